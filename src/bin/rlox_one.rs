@@ -3,10 +3,21 @@
 //! Java implementation as possible, and only deviates when required by differences in the language. Therefore, it's not very
 //! "rusty". WE will try much harder to rustify the port of the final C implementation.
 
+use std::thread;
+
 use rust_lox_impl::lox::Lox;
 
+/// Every Lox call recurses through the interpreter, so it gets a much bigger stack than
+/// the default to be able to reach its call depth limit before the real stack runs out.
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
     // We use a class here because the java impl uses static members of the class
     // main resides in for state. So we sort of needed a "Main Class".
-    Lox::new().lox_main();
+    thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(|| Lox::new().lox_main())
+        .expect("Couldn't start the interpreter thread")
+        .join()
+        .expect("The interpreter thread panicked");
 }
@@ -8,6 +8,6 @@ pub trait ThenTry {
 /// a bunch of combinators that bubble up errors for using ? on.
 impl ThenTry for bool {
     fn then_try<T, E>(self, f: impl FnOnce() -> Result<T, E>) -> Result<Option<T>, E> {
-        self.then(|| f()).transpose()
+        self.then(f).transpose()
     }
 }
@@ -1,10 +1,21 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
-    callable::LoxCallable, environment::Environment, interpreter::Interpreter, object::LoxObject,
+    callable::LoxCallable,
+    environment::Environment,
+    error::runtime_error::RuntimeError,
+    interpreter::{Interpreter, RuntimeResult},
+    object::LoxObject,
 };
 
-/// Built in function clock, used for benchmarking inside a lox script
+/// Built in function clock, used for benchmarking inside a lox script. Reads
+/// the time from the interpreter's clock.
 #[derive(Clone, PartialEq, Debug)]
 pub struct Clock {}
 
@@ -13,22 +24,84 @@ impl LoxCallable for Clock {
         0usize
     }
 
-    fn call(&self, _: &mut Interpreter, _: &mut Environment, _: Vec<LoxObject>) -> LoxObject {
-        LoxObject::Number(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs_f64(),
-        )
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _: &mut Environment,
+        _: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Number((interpreter.clock)()))
     }
 }
 
 impl std::fmt::Display for Clock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<fn cllock>")
+        write!(f, "<fn clock>")
+    }
+}
+
+/// Built in function clock_millis, which returns the time in whole milliseconds, for
+/// timing things too quick for `clock` to be useful.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockMillis {}
+
+impl LoxCallable for ClockMillis {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _: &mut Environment,
+        _: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let seconds = (interpreter.clock)();
+        Ok(LoxObject::Number((seconds * 1000.0).floor()))
+    }
+}
+
+impl std::fmt::Display for ClockMillis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn clock_millis>")
+    }
+}
+
+/// Built in function sleep, which pauses the script for a number of milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sleep {}
+
+impl LoxCallable for Sleep {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _: &mut Interpreter,
+        _: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let millis = number_arg("sleep", &args[0])?;
+        let duration = Duration::try_from_secs_f64(millis / 1000.0).map_err(|_| {
+            RuntimeError::native(format!("Can't sleep for {} milliseconds.", args[0]))
+        })?;
+        thread::sleep(duration);
+        Ok(LoxObject::Nil)
+    }
+}
+
+impl std::fmt::Display for Sleep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn sleep>")
     }
 }
 
+/// The error raised when a builtin can't write to the interpreter's output.
+fn output_error(e: io::Error) -> RuntimeError {
+    RuntimeError::native(format!("Couldn't write output: {}", e))
+}
+
 /// Built in function print_env, for printing out the different memory scopes
 /// and variables at a given point in a lox script. Useful for debugging in a lox script.
 #[derive(Debug, Clone, PartialEq)]
@@ -41,12 +114,12 @@ impl LoxCallable for PrintEnv {
 
     fn call(
         &self,
-        _interpreter: &mut Interpreter,
+        interpreter: &mut Interpreter,
         env: &mut Environment,
         _: Vec<LoxObject>,
-    ) -> LoxObject {
-        println!("{}", env);
-        LoxObject::Nil
+    ) -> RuntimeResult<LoxObject> {
+        writeln!(interpreter.output, "{}", env).map_err(output_error)?;
+        Ok(LoxObject::Nil)
     }
 }
 
@@ -56,22 +129,90 @@ impl std::fmt::Display for PrintEnv {
     }
 }
 
+/// Built in function globals, which returns an array of the names of every global
+/// variable (builtins included) in alphabetical order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Globals {}
+
+impl LoxCallable for Globals {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        env: &mut Environment,
+        _: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(names_array(env.global_names()))
+    }
+}
+
+impl std::fmt::Display for Globals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn globals>")
+    }
+}
+
+/// Built in function locals, which returns an array of the names of the variables
+/// in the innermost scope where it's called, in alphabetical order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locals {}
+
+impl LoxCallable for Locals {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        env: &mut Environment,
+        _: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(names_array(env.local_names()))
+    }
+}
+
+impl std::fmt::Display for Locals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn locals>")
+    }
+}
+
+/// Wraps up a list of variable names as a Lox array of strings.
+fn names_array(names: Vec<String>) -> LoxObject {
+    LoxObject::Array(Rc::new(RefCell::new(
+        names.into_iter().map(LoxObject::String).collect(),
+    )))
+}
+
+/// Built in function print, which prints any number of values separated by spaces,
+/// followed by a newline. The output is flushed straight away, so it can't end up
+/// out of order with errors when stdout is redirected.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Print {}
 
 impl LoxCallable for Print {
     fn arity(&self) -> usize {
-        1usize
+        0usize
+    }
+
+    fn max_arity(&self) -> Option<usize> {
+        None
     }
 
     fn call(
         &self,
-        _interpreter: &mut Interpreter,
+        interpreter: &mut Interpreter,
         _env: &mut Environment,
         args: Vec<LoxObject>,
-    ) -> LoxObject {
-        println!("{}", args[0]);
-        LoxObject::Nil
+    ) -> RuntimeResult<LoxObject> {
+        write_values(interpreter, &args)?;
+        writeln!(interpreter.output).map_err(output_error)?;
+        interpreter.output.flush().map_err(output_error)?;
+        Ok(LoxObject::Nil)
     }
 }
 
@@ -80,3 +221,1316 @@ impl std::fmt::Display for Print {
         write!(f, "<fn print>")
     }
 }
+
+/// Built in function write, which works like print but without the trailing newline,
+/// so a line can be built up over several calls. (Named so it doesn't clash with
+/// `std::io::Write`.)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintNoNewline {}
+
+impl LoxCallable for PrintNoNewline {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    fn max_arity(&self) -> Option<usize> {
+        None
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        write_values(interpreter, &args)?;
+        interpreter.output.flush().map_err(output_error)?;
+        Ok(LoxObject::Nil)
+    }
+}
+
+impl std::fmt::Display for PrintNoNewline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn write>")
+    }
+}
+
+/// Writes values to the interpreter's output separated by spaces.
+fn write_values(interpreter: &mut Interpreter, values: &[LoxObject]) -> RuntimeResult<()> {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(interpreter.output, " ").map_err(output_error)?;
+        }
+        write!(interpreter.output, "{}", value).map_err(output_error)?;
+    }
+    Ok(())
+}
+
+/// Built in function input, which prints a prompt (if it's given one) and then reads a
+/// line of input (from stdin, unless the interpreter has been given a different input).
+/// The trailing newline is stripped, and nil is returned once the input runs out.
+/// # Example
+/// ```
+/// use std::io::Cursor;
+/// use rust_lox_impl::interpreter::Interpreter;
+///
+/// let mut interpreter = Interpreter::new();
+/// interpreter.input = Box::new(Cursor::new("Ada\n"));
+/// let (output, _) = interpreter.run_to_string("print(input(\"Name? \")); print(input());");
+/// assert_eq!(output, "Name? Ada\nnil\n");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {}
+
+impl LoxCallable for Input {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    fn max_arity(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        // Print the prompt without a newline, so it needs an explicit flush.
+        if let Some(prompt) = args.first() {
+            write!(interpreter.output, "{}", prompt).map_err(output_error)?;
+            interpreter.output.flush().map_err(output_error)?;
+        }
+
+        let mut line = String::new();
+        match interpreter.input.read_line(&mut line) {
+            Ok(0) => Ok(LoxObject::Nil),
+            Ok(_) => {
+                let line = line.strip_suffix('\n').unwrap_or(&line);
+                let line = line.strip_suffix('\r').unwrap_or(line);
+                Ok(LoxObject::String(line.to_owned()))
+            }
+            Err(e) => Err(RuntimeError::native(format!("Couldn't read input: {}", e))),
+        }
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn input>")
+    }
+}
+
+/// Built in function len, for getting the number of characters in a string
+/// or the number of elements in an array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Len {}
+
+impl LoxCallable for Len {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        match &args[0] {
+            LoxObject::String(s) => Ok(LoxObject::Number(s.chars().count() as f64)),
+            LoxObject::Array(elements) => Ok(LoxObject::Number(elements.borrow().len() as f64)),
+            _ => Err(RuntimeError::native(
+                "Can only take the length of a string or an array.",
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Len {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn len>")
+    }
+}
+
+/// Built in function type, which returns the name of the type of its argument
+/// as a string, i.e. `type(3) == "number"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type {}
+
+impl LoxCallable for Type {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::String(args[0].type_name().to_owned()))
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn type>")
+    }
+}
+
+/// Built in function which converts its argument to a boolean using the same rules
+/// as `if` and `while`, i.e. `bool(nil) == false` but `bool("") == true`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bool {}
+
+impl LoxCallable for Bool {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Boolean(interpreter.is_truthy(&args[0])))
+    }
+}
+
+impl std::fmt::Display for Bool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn bool>")
+    }
+}
+
+/// Built in function equals, which compares two values by their contents rather than
+/// by reference, so two arrays holding equal elements are equal even though `==`
+/// says they're different arrays.
+/// # Example
+/// ```
+/// use rust_lox_impl::lox::run_source;
+///
+/// assert!(run_source("assert(equals([1, [2]], [1, [2]]));").is_ok());
+/// assert!(run_source("assert(!equals([1, 2], [2, 1]));").is_ok());
+/// assert!(run_source("assert([1] != [1]);").is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Equals {}
+
+impl LoxCallable for Equals {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Boolean(args[0].equals(&args[1])))
+    }
+}
+
+impl std::fmt::Display for Equals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn equals>")
+    }
+}
+
+/// Built in function clone, which makes a shallow copy of an array or instance, so it
+/// can be changed without changing the original. The elements or fields themselves
+/// aren't copied. Anything else is already a value, so it's returned as it is.
+/// # Example
+/// ```
+/// use rust_lox_impl::lox::run_source;
+///
+/// assert!(run_source("var a = [[1]]; var b = clone(a); assert(a != b and a[0] == b[0]);").is_ok());
+/// assert!(run_source("assert_eq(clone(3), 3);").is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShallowClone {}
+
+impl LoxCallable for ShallowClone {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(match &args[0] {
+            LoxObject::Array(elements) => {
+                LoxObject::Array(Rc::new(RefCell::new(elements.borrow().clone())))
+            }
+            LoxObject::Instance(instance) => {
+                LoxObject::Instance(Rc::new(RefCell::new(instance.borrow().clone())))
+            }
+            other => other.clone(),
+        })
+    }
+}
+
+impl std::fmt::Display for ShallowClone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn clone>")
+    }
+}
+
+/// Extracts a number from an argument to a native function, or produces an error
+/// naming the function if the argument isn't a number.
+fn number_arg(function: &str, arg: &LoxObject) -> RuntimeResult<f64> {
+    if let LoxObject::Number(n) = arg {
+        Ok(*n)
+    } else {
+        Err(RuntimeError::native(format!(
+            "{}() expects a number but got {}.",
+            function,
+            arg.type_name()
+        )))
+    }
+}
+
+/// Extracts a string from an argument to a native function, or produces an error
+/// naming the function if the argument isn't a string.
+fn string_arg<'a>(function: &str, arg: &'a LoxObject) -> RuntimeResult<&'a str> {
+    if let LoxObject::String(s) = arg {
+        Ok(s)
+    } else {
+        Err(RuntimeError::native(format!(
+            "{}() expects a string but got {}.",
+            function,
+            arg.type_name()
+        )))
+    }
+}
+
+/// Extracts the elements of an array argument to a native function, or produces an
+/// error naming the function if the argument isn't an array.
+fn array_arg(function: &str, arg: &LoxObject) -> RuntimeResult<Rc<RefCell<Vec<LoxObject>>>> {
+    if let LoxObject::Array(elements) = arg {
+        Ok(Rc::clone(elements))
+    } else {
+        Err(RuntimeError::native(format!(
+            "{}() expects an array but got {}.",
+            function,
+            arg.type_name()
+        )))
+    }
+}
+
+/// Extracts a function (or class) from an argument to a native function which will be
+/// called back with `arg_count` arguments, checking up front that it can take that many.
+fn callback_arg(
+    function: &str,
+    arg: &LoxObject,
+    arg_count: usize,
+) -> RuntimeResult<Box<dyn LoxCallable>> {
+    let callback: Box<dyn LoxCallable> = match arg {
+        LoxObject::Function(callback) => callback.clone(),
+        LoxObject::Class(class) => Box::new(class.clone()),
+        _ => {
+            return Err(RuntimeError::native(format!(
+                "{}() expects a function but got {}.",
+                function,
+                arg.type_name()
+            )))
+        }
+    };
+
+    if callback.accepts(arg_count) {
+        Ok(callback)
+    } else {
+        Err(RuntimeError::native(format!(
+            "{}() calls its function with {} arguments, but {} takes {}.",
+            function,
+            arg_count,
+            callback,
+            callback.expected_args()
+        )))
+    }
+}
+
+/// Extracts a position (a whole, non-negative number) from an argument to a native function.
+fn position_arg(function: &str, arg: &LoxObject) -> RuntimeResult<usize> {
+    let n = number_arg(function, arg)?;
+    if n.fract() != 0.0 || n < 0.0 {
+        Err(RuntimeError::native(format!(
+            "{}() expects a whole, non-negative number but got {}.",
+            function, arg
+        )))
+    } else {
+        Ok(n as usize)
+    }
+}
+
+/// Built in function sqrt, for taking the square root of a number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sqrt {}
+
+impl LoxCallable for Sqrt {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let n = number_arg("sqrt", &args[0])?;
+        if n < 0.0 {
+            Err(RuntimeError::native(
+                "Cannot take the square root of a negative number.",
+            ))
+        } else {
+            Ok(LoxObject::Number(n.sqrt()))
+        }
+    }
+}
+
+impl std::fmt::Display for Sqrt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn sqrt>")
+    }
+}
+
+/// Built in function floor, for rounding a number down to the nearest whole number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Floor {}
+
+impl LoxCallable for Floor {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Number(number_arg("floor", &args[0])?.floor()))
+    }
+}
+
+impl std::fmt::Display for Floor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn floor>")
+    }
+}
+
+/// Built in function ceil, for rounding a number up to the nearest whole number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ceil {}
+
+impl LoxCallable for Ceil {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Number(number_arg("ceil", &args[0])?.ceil()))
+    }
+}
+
+impl std::fmt::Display for Ceil {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn ceil>")
+    }
+}
+
+/// Built in function round, for rounding a number to the nearest whole number.
+/// Halves round away from zero, so `round(2.5) == 3` and `round(-2.5) == -3`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Round {}
+
+impl LoxCallable for Round {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Number(number_arg("round", &args[0])?.round()))
+    }
+}
+
+impl std::fmt::Display for Round {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn round>")
+    }
+}
+
+/// Built in function abs, for taking the absolute value of a number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Abs {}
+
+impl LoxCallable for Abs {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Number(number_arg("abs", &args[0])?.abs()))
+    }
+}
+
+impl std::fmt::Display for Abs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn abs>")
+    }
+}
+
+/// Built in function pow, for raising a number to a power, i.e. `pow(2, 10) == 1024`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pow {}
+
+impl LoxCallable for Pow {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let base = number_arg("pow", &args[0])?;
+        let exponent = number_arg("pow", &args[1])?;
+        Ok(LoxObject::Number(base.powf(exponent)))
+    }
+}
+
+impl std::fmt::Display for Pow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn pow>")
+    }
+}
+
+/// Built in function min, which returns the smallest of any number of numbers,
+/// i.e. `min(3, 1, 2) == 1`. It needs at least one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Min {}
+
+impl LoxCallable for Min {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn max_arity(&self) -> Option<usize> {
+        None
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let smallest = args.iter().try_fold(f64::INFINITY, |min, arg| {
+            Ok(min.min(number_arg("min", arg)?))
+        })?;
+        Ok(LoxObject::Number(smallest))
+    }
+}
+
+impl std::fmt::Display for Min {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn min>")
+    }
+}
+
+/// Built in function max, which returns the largest of any number of numbers,
+/// i.e. `max(3, 1, 2) == 3`. It needs at least one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Max {}
+
+impl LoxCallable for Max {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn max_arity(&self) -> Option<usize> {
+        None
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let largest = args.iter().try_fold(f64::NEG_INFINITY, |max, arg| {
+            Ok(max.max(number_arg("max", arg)?))
+        })?;
+        Ok(LoxObject::Number(largest))
+    }
+}
+
+impl std::fmt::Display for Max {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn max>")
+    }
+}
+
+/// Built in function to_number, which parses a string into a number. Returns nil
+/// if the string isn't a valid (finite) number, so scripts can check for bad input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToNumber {}
+
+impl LoxCallable for ToNumber {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        match &args[0] {
+            // Rust also parses "nan", "inf" and numbers too big to fit, none of which
+            // Lox can otherwise produce.
+            LoxObject::String(s) => Ok(s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(LoxObject::Number)
+                .unwrap_or(LoxObject::Nil)),
+            LoxObject::Number(n) => Ok(LoxObject::Number(*n)),
+            other => Err(RuntimeError::native(format!(
+                "to_number() expects a string or number but got {}.",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ToNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn to_number>")
+    }
+}
+
+/// Built in function to_string, which converts any value into a string the
+/// same way `print` would display it. Named `ToStr` so it doesn't shadow the
+/// std `ToString` trait.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToStr {}
+
+impl LoxCallable for ToStr {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::String(args[0].to_string()))
+    }
+}
+
+impl std::fmt::Display for ToStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn to_string>")
+    }
+}
+
+/// Built in function to_fixed, which converts a number to a string with a set number
+/// of decimal places (up to 100), i.e. `to_fixed(3.14159, 2) == "3.14"` and `to_fixed(1, 2) == "1.00"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToFixed {}
+
+impl LoxCallable for ToFixed {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let n = number_arg("to_fixed", &args[0])?;
+        let digits = position_arg("to_fixed", &args[1])?;
+
+        // Same limit as JavaScript's toFixed, well past where an f64 has any precision left.
+        if digits > 100 {
+            return Err(RuntimeError::native(format!(
+                "to_fixed() can show at most 100 decimal places, not {}.",
+                digits
+            )));
+        }
+        Ok(LoxObject::String(format!("{:.*}", digits, n)))
+    }
+}
+
+impl std::fmt::Display for ToFixed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn to_fixed>")
+    }
+}
+
+/// Built in function char_at, which returns the character at an index in a string
+/// as a one character string, i.e. `char_at("hello", 1) == "e"`. Indexes count
+/// characters rather than bytes, and an index past the end is an error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharAt {}
+
+impl LoxCallable for CharAt {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let s = string_arg("char_at", &args[0])?;
+        let index = position_arg("char_at", &args[1])?;
+        s.chars()
+            .nth(index)
+            .map(|c| LoxObject::String(c.to_string()))
+            .ok_or_else(|| {
+                RuntimeError::native(format!(
+                    "Index {} out of bounds for string of length {}",
+                    index,
+                    s.chars().count()
+                ))
+            })
+    }
+}
+
+impl std::fmt::Display for CharAt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn char_at>")
+    }
+}
+
+/// Built in function substring, which returns the characters of a string from the
+/// start index up to (but not including) the end index, i.e.
+/// `substring("hello", 1, 3) == "el"`. Indexes count characters rather than bytes.
+/// Bounds aren't clamped: an end past the length of the string, or a start after
+/// the end, is an error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substring {}
+
+impl LoxCallable for Substring {
+    fn arity(&self) -> usize {
+        3usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let s = string_arg("substring", &args[0])?;
+        let start = position_arg("substring", &args[1])?;
+        let end = position_arg("substring", &args[2])?;
+
+        let len = s.chars().count();
+        if start > end || end > len {
+            return Err(RuntimeError::native(format!(
+                "Range {}..{} out of bounds for string of length {}",
+                start, end, len
+            )));
+        }
+        Ok(LoxObject::String(
+            s.chars().skip(start).take(end - start).collect(),
+        ))
+    }
+}
+
+impl std::fmt::Display for Substring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn substring>")
+    }
+}
+
+/// Built in function split, which breaks a string into an array of the pieces between
+/// each occurrence of a separator, i.e. `split("a,b", ",")` is `["a", "b"]`.
+/// Splitting on an empty separator breaks the string into its characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Split {}
+
+impl LoxCallable for Split {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let s = string_arg("split", &args[0])?;
+        let separator = string_arg("split", &args[1])?;
+
+        let pieces: Vec<LoxObject> = if separator.is_empty() {
+            s.chars()
+                .map(|c| LoxObject::String(c.to_string()))
+                .collect()
+        } else {
+            s.split(separator)
+                .map(|piece| LoxObject::String(piece.to_owned()))
+                .collect()
+        };
+        Ok(LoxObject::Array(Rc::new(RefCell::new(pieces))))
+    }
+}
+
+impl std::fmt::Display for Split {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn split>")
+    }
+}
+
+/// Built in function join, which glues the elements of an array together into a
+/// string with a separator between each, i.e. `join(["a", "b"], "-") == "a-b"`.
+/// Elements that aren't strings are converted the same way `print` would show them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {}
+
+impl LoxCallable for Join {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("join", &args[0])?;
+        let separator = string_arg("join", &args[1])?;
+
+        let pieces: Vec<String> = elements.borrow().iter().map(|e| e.to_string()).collect();
+        Ok(LoxObject::String(pieces.join(separator)))
+    }
+}
+
+impl std::fmt::Display for Join {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn join>")
+    }
+}
+
+/// Built in function push, which adds a value to the end of an array and returns
+/// the new length. Arrays are shared, so every variable holding it sees the change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Push {}
+
+impl LoxCallable for Push {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("push", &args[0])?;
+        let mut elements = elements.borrow_mut();
+        elements.push(args[1].clone());
+        Ok(LoxObject::Number(elements.len() as f64))
+    }
+}
+
+impl std::fmt::Display for Push {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn push>")
+    }
+}
+
+/// Built in function pop, which removes the last element of an array and returns it,
+/// or returns nil if the array is empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pop {}
+
+impl LoxCallable for Pop {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("pop", &args[0])?;
+        let popped = elements.borrow_mut().pop();
+        Ok(popped.unwrap_or(LoxObject::Nil))
+    }
+}
+
+impl std::fmt::Display for Pop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn pop>")
+    }
+}
+
+/// Built in function insert, which puts a value into an array at an index, shifting
+/// everything after it along, i.e. `insert(arr, 0, x)` adds `x` to the front. The index
+/// can be the length of the array (to add to the end), but no further.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {}
+
+impl LoxCallable for Insert {
+    fn arity(&self) -> usize {
+        3usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("insert", &args[0])?;
+        let index = position_arg("insert", &args[1])?;
+
+        let mut elements = elements.borrow_mut();
+        if index > elements.len() {
+            return Err(RuntimeError::native(format!(
+                "Index {} out of bounds for array of length {}",
+                index,
+                elements.len()
+            )));
+        }
+        elements.insert(index, args[2].clone());
+        Ok(LoxObject::Nil)
+    }
+}
+
+impl std::fmt::Display for Insert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn insert>")
+    }
+}
+
+/// Built in function contains, which checks whether an array has an element equal to
+/// a value, or whether a string has another string inside it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contains {}
+
+impl LoxCallable for Contains {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let index = find("contains", &args[0], &args[1])?;
+        Ok(LoxObject::Boolean(index.is_some()))
+    }
+}
+
+impl std::fmt::Display for Contains {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn contains>")
+    }
+}
+
+/// Built in function index_of, which returns the index of the first element of an array
+/// equal to a value, or of the first place a string appears inside another. Returns -1
+/// if there isn't one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexOf {}
+
+impl LoxCallable for IndexOf {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let index = find("index_of", &args[0], &args[1])?;
+        Ok(LoxObject::Number(index.map_or(-1.0, |i| i as f64)))
+    }
+}
+
+impl std::fmt::Display for IndexOf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn index_of>")
+    }
+}
+
+/// Searches an array or string for a value, the same way `==` compares them. Indexes
+/// into strings count characters rather than bytes, like `char_at` does.
+fn find(function: &str, haystack: &LoxObject, needle: &LoxObject) -> RuntimeResult<Option<usize>> {
+    match haystack {
+        LoxObject::Array(elements) => Ok(elements.borrow().iter().position(|e| e == needle)),
+        LoxObject::String(s) => {
+            let needle = string_arg(function, needle)?;
+            Ok(s.find(needle).map(|byte| s[..byte].chars().count()))
+        }
+        _ => Err(RuntimeError::native(format!(
+            "{}() expects an array or a string but got {}.",
+            function,
+            haystack.type_name()
+        ))),
+    }
+}
+
+/// Built in function map, which calls a function on each element of an array and
+/// returns a new array of the results, i.e. `map([1, 2], double)` is `[2, 4]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {}
+
+impl LoxCallable for Map {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("map", &args[0])?;
+        let callback = callback_arg("map", &args[1], 1)?;
+
+        // The callback could change the array, so don't hold a borrow while calling it.
+        let elements = elements.borrow().clone();
+        let mapped = elements
+            .into_iter()
+            .map(|element| callback.call(interpreter, env, vec![element]))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        Ok(LoxObject::Array(Rc::new(RefCell::new(mapped))))
+    }
+}
+
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn map>")
+    }
+}
+
+/// Built in function filter, which returns a new array of the elements of an array
+/// that a function returns something truthy for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {}
+
+impl LoxCallable for Filter {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("filter", &args[0])?;
+        let callback = callback_arg("filter", &args[1], 1)?;
+
+        let elements = elements.borrow().clone();
+        let mut kept = vec![];
+        for element in elements {
+            let keep = callback.call(interpreter, env, vec![element.clone()])?;
+            if interpreter.is_truthy(&keep) {
+                kept.push(element);
+            }
+        }
+        Ok(LoxObject::Array(Rc::new(RefCell::new(kept))))
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn filter>")
+    }
+}
+
+/// Built in function reduce, which combines the elements of an array into a single
+/// value. The function is called with the running total and each element in turn,
+/// starting from the initial value, i.e. `reduce([1, 2, 3], add, 0) == 6`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reduce {}
+
+impl LoxCallable for Reduce {
+    fn arity(&self) -> usize {
+        3usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = array_arg("reduce", &args[0])?;
+        let callback = callback_arg("reduce", &args[1], 2)?;
+
+        let elements = elements.borrow().clone();
+        elements
+            .into_iter()
+            .try_fold(args[2].clone(), |total, element| {
+                callback.call(interpreter, env, vec![total, element])
+            })
+    }
+}
+
+impl std::fmt::Display for Reduce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn reduce>")
+    }
+}
+
+/// A small xorshift pseudo-random number generator. Nowhere near good enough for
+/// cryptography, but plenty for games and simulations, and seeding it makes a
+/// script's "random" numbers repeatable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator that always produces the same sequence for the same seed.
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed (with a step of splitmix64) so that similar seeds don't give
+        // similar sequences. Xorshift gets stuck on zero, so that's avoided too.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    /// Creates a generator seeded from the current time.
+    pub fn from_clock() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new(nanos)
+    }
+
+    /// Produces the next number in the sequence, in the range [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        // The top 53 bits fill the mantissa of a double exactly.
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Built in function random, which returns a random number from 0 up to (but not including) 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Random {}
+
+impl LoxCallable for Random {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        _args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Number(interpreter.rng.next_f64()))
+    }
+}
+
+impl std::fmt::Display for Random {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn random>")
+    }
+}
+
+/// Built in function random_int, which returns a random whole number between
+/// two whole numbers, including both ends, i.e. `random_int(1, 6)` rolls a die.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomInt {}
+
+impl LoxCallable for RandomInt {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let lo = number_arg("random_int", &args[0])?;
+        let hi = number_arg("random_int", &args[1])?;
+        if lo.fract() != 0.0 || hi.fract() != 0.0 || lo > hi {
+            return Err(RuntimeError::native(format!(
+                "random_int() expects two whole numbers with the lowest first, but got {} and {}.",
+                args[0], args[1]
+            )));
+        }
+
+        let offset = (interpreter.rng.next_f64() * (hi - lo + 1.0)).floor();
+        Ok(LoxObject::Number(lo + offset))
+    }
+}
+
+impl std::fmt::Display for RandomInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn random_int>")
+    }
+}
+
+/// Built in function seed_random, which reseeds the random number generator so
+/// the numbers that follow are the same every time the script runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedRandom {}
+
+impl LoxCallable for SeedRandom {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        let seed = number_arg("seed_random", &args[0])?;
+        interpreter.rng = Rng::new(seed.to_bits());
+        Ok(LoxObject::Nil)
+    }
+}
+
+impl std::fmt::Display for SeedRandom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn seed_random>")
+    }
+}
+
+/// Built in function assert, which raises an error if its argument isn't truthy.
+/// Handy for writing Lox scripts that check themselves.
+/// # Example
+/// ```
+/// use rust_lox_impl::lox::run_source;
+///
+/// assert!(run_source("assert(1 < 2);").is_ok());
+/// let errors = run_source("assert(1 > 2);").unwrap_err();
+/// assert!(errors[0].starts_with("[Line 1, Col 13] Error at ')': Assertion failed"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assert {}
+
+impl LoxCallable for Assert {
+    fn arity(&self) -> usize {
+        1usize
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        if interpreter.is_truthy(&args[0]) {
+            Ok(LoxObject::Nil)
+        } else {
+            Err(RuntimeError::native("Assertion failed"))
+        }
+    }
+}
+
+impl std::fmt::Display for Assert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn assert>")
+    }
+}
+
+/// Built in function assert_eq, which raises an error showing both values if they
+/// aren't equal (the same way `equals` compares them, so arrays are compared by contents).
+/// # Example
+/// ```
+/// use rust_lox_impl::lox::run_source;
+///
+/// assert!(run_source("assert_eq(1 + 1, 2);").is_ok());
+/// let errors = run_source("assert_eq([1], [2]);").unwrap_err();
+/// assert!(errors[0].starts_with("[Line 1, Col 19] Error at ')': Assertion failed: [1] != [2]"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertEq {}
+
+impl LoxCallable for AssertEq {
+    fn arity(&self) -> usize {
+        2usize
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _env: &mut Environment,
+        args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        if args[0].equals(&args[1]) {
+            Ok(LoxObject::Nil)
+        } else {
+            Err(RuntimeError::native(format!(
+                "Assertion failed: {} != {}",
+                args[0], args[1]
+            )))
+        }
+    }
+}
+
+impl std::fmt::Display for AssertEq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn assert_eq>")
+    }
+}
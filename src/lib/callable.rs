@@ -1,20 +1,67 @@
 use dyn_clone::DynClone;
 
-use crate::{environment::Environment, interpreter::Interpreter, object::LoxObject};
+use crate::{
+    environment::Environment,
+    function::LoxFunction,
+    interpreter::{Interpreter, RuntimeResult},
+    object::LoxObject,
+};
 
 /// This trait is implemented on any Lox Structure that acts like a function
 /// Requires Clone and Display. We also implement Clone for Box<dyn LoxCallable>
 /// so that we can have a clonable trait object.
 pub trait LoxCallable: DynClone + std::fmt::Display {
-    /// The number of parameters
+    /// The number of parameters, or the least number of arguments it can be called
+    /// with if some parameters are optional.
     fn arity(&self) -> usize;
 
-    /// Calls the thing and returns a Lox Object
+    /// The most arguments it can be called with, or `None` if there's no limit.
+    /// Only needs overriding for things with optional parameters.
+    fn max_arity(&self) -> Option<usize> {
+        Some(self.arity())
+    }
+
+    /// What to call it in error messages. Defaults to how it's displayed, like `<fn len>`.
+    fn name(&self) -> String {
+        self.to_string()
+    }
+
+    /// The function declared in Lox, if that's what it is. Lets the interpreter
+    /// spot tail calls.
+    fn as_function(&self) -> Option<&LoxFunction> {
+        None
+    }
+
+    /// Whether it can be called with the given number of arguments.
+    fn accepts(&self, arg_count: usize) -> bool {
+        arg_count >= self.arity() && self.max_arity().is_none_or(|max| arg_count <= max)
+    }
+
+    /// Describes how many arguments it takes for error messages, i.e. "2", "1 to 3", or "at least 1".
+    fn expected_args(&self) -> String {
+        match self.max_arity() {
+            Some(max) if max == self.arity() => max.to_string(),
+            Some(max) => format!("{} to {}", self.arity(), max),
+            None => format!("at least {}", self.arity()),
+        }
+    }
+
+    /// Calls the thing and returns a Lox Object, or the runtime error it hit. Errors from
+    /// builtins are reported like any other runtime error, at the call that caused them.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::lox::run_source;
+    ///
+    /// let errors = run_source("len(5);").unwrap_err();
+    /// assert!(errors[0].starts_with(
+    ///     "[Line 1, Col 6] Error at ')': Can only take the length of a string or an array."
+    /// ));
+    /// ```
     fn call(
         &self,
         interpreter: &mut Interpreter,
         env: &mut Environment,
         args: Vec<LoxObject>,
-    ) -> LoxObject;
+    ) -> RuntimeResult<LoxObject>;
 }
 dyn_clone::clone_trait_object!(LoxCallable);
@@ -0,0 +1,107 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    callable::LoxCallable,
+    environment::Environment,
+    error::runtime_error::RuntimeError,
+    function::LoxFunction,
+    instance::LoxInstance,
+    interpreter::{Interpreter, RuntimeResult},
+    object::LoxObject,
+    token::Token,
+};
+
+/// Represents a Lox Class Object. Calling a class constructs a new instance of it.
+#[derive(Clone)]
+pub struct LoxClass {
+    /// The token of the class name from the class declaration
+    name: Token,
+
+    /// The methods declared in the body of the class. Kept behind an Rc because
+    /// every instance holds a copy of its class.
+    methods: Rc<HashMap<String, LoxFunction>>,
+
+    /// The getters declared in the body of the class, which are called as soon as
+    /// they're accessed.
+    getters: Rc<HashMap<String, LoxFunction>>,
+
+    /// The methods declared with a `class` prefix, which are called on the class itself.
+    class_methods: Rc<HashMap<String, LoxFunction>>,
+}
+
+impl LoxClass {
+    /// Construct a class object from its name and its (already closed over) methods,
+    /// getters and class methods.
+    pub fn new(
+        name: Token,
+        methods: HashMap<String, LoxFunction>,
+        getters: HashMap<String, LoxFunction>,
+        class_methods: HashMap<String, LoxFunction>,
+    ) -> Self {
+        Self {
+            name,
+            methods: Rc::new(methods),
+            getters: Rc::new(getters),
+            class_methods: Rc::new(class_methods),
+        }
+    }
+
+    /// The name of the class
+    pub fn name(&self) -> &str {
+        &self.name.lexeme
+    }
+
+    /// Looks up a method declared on the class
+    pub fn find_method(&self, name: &str) -> Option<&LoxFunction> {
+        self.methods.get(name)
+    }
+
+    /// Looks up a getter declared on the class
+    pub fn find_getter(&self, name: &str) -> Option<&LoxFunction> {
+        self.getters.get(name)
+    }
+
+    /// Looks up a class method, bound to the class so that `this` inside it is the class.
+    pub fn get(&self, name: &Token) -> RuntimeResult<LoxObject> {
+        match self.class_methods.get(&name.lexeme) {
+            Some(method) => Ok(LoxObject::Function(Box::new(
+                method.bind(LoxObject::Class(self.clone())),
+            ))),
+            None => Err(RuntimeError::new(
+                name.clone(),
+                format!("Undefined property '{}'.", name.lexeme),
+            )),
+        }
+    }
+}
+
+/// Two class objects are only the same class if they came from the same declaration.
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.methods, &other.methods)
+    }
+}
+
+impl LoxCallable for LoxClass {
+    fn arity(&self) -> usize {
+        0usize
+    }
+
+    /// Constructs a new instance of the class.
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _exec_env: &mut Environment,
+        _args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        Ok(LoxObject::Instance(Rc::new(RefCell::new(
+            LoxInstance::new(self.clone()),
+        ))))
+    }
+}
+
+impl std::fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name.lexeme)
+    }
+}
@@ -1,56 +1,113 @@
-use std::collections::{HashMap, LinkedList};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
-    builtin_functions::{Clock, Print, PrintEnv},
+    builtin_functions::{
+        Abs, Assert, AssertEq, Bool, Ceil, CharAt, Clock, ClockMillis, Contains, Equals, Filter,
+        Floor, Globals, IndexOf, Input, Insert, Join, Len, Locals, Map, Max, Min, Pop, Pow, Print,
+        PrintEnv, PrintNoNewline, Push, Random, RandomInt, Reduce, Round, SeedRandom, ShallowClone,
+        Sleep, Split, Sqrt, Substring, ToFixed, ToNumber, ToStr, Type,
+    },
     error::runtime_error::{RuntimeError, RuntimeErrorCtx},
     interpreter::RuntimeResult,
     object::LoxObject,
     token::Token,
 };
 
-/// Represents a single scope of LoxObjects
-#[derive(Clone, PartialEq)]
-pub struct Scope(HashMap<String, LoxObject>);
+/// Represents a single scope of LoxObjects.
+/// Cloning a Scope is cheap and produces a handle to the *same* scope, which is what
+/// lets a closure share variables with the scope it was declared in.
+#[derive(Clone)]
+pub struct Scope(Rc<RefCell<HashMap<String, Binding>>>);
+
+/// The value of a variable, along with whether it was declared `const`.
+#[derive(Clone)]
+struct Binding {
+    value: LoxObject,
+    is_const: bool,
+}
 
 impl Scope {
     /// Creates a new Scope
     pub fn new() -> Self {
-        Scope(HashMap::new())
+        Scope(Rc::new(RefCell::new(HashMap::new())))
     }
 
     /// Sets the value for a variable in the given scope. Optionally returns the old obj
     /// if the variable was previously defined.
     pub fn define(&mut self, name: &str, value: LoxObject) -> Option<LoxObject> {
-        self.0.insert(name.to_owned(), value)
+        self.insert(name, value, false)
+    }
+
+    /// Like `define`, but marks the variable as a constant. The scope itself doesn't
+    /// stop a constant being reassigned, `Environment::assign_at` checks `is_const` first.
+    pub fn define_const(&mut self, name: &str, value: LoxObject) -> Option<LoxObject> {
+        self.insert(name, value, true)
+    }
+
+    fn insert(&mut self, name: &str, value: LoxObject, is_const: bool) -> Option<LoxObject> {
+        self.0
+            .borrow_mut()
+            .insert(name.to_owned(), Binding { value, is_const })
+            .map(|old| old.value)
     }
 
     /// If a variable is already defined in the scope, reassigns
     /// it and returns the old value. Returns `None` if the variable is not
     /// yet defined.
     pub fn assign(&mut self, name: &str, value: LoxObject) -> Option<LoxObject> {
-        if self.0.contains_key(name) {
-            self.0.insert(name.to_owned(), value)
-        } else {
-            None
-        }
+        self.0
+            .borrow_mut()
+            .get_mut(name)
+            .map(|binding| std::mem::replace(&mut binding.value, value))
     }
 
     /// Tries to retrieve a variable from the scope
     pub fn get(&self, name: &str) -> Option<LoxObject> {
-        self.0.get(name).map(|obj| obj.clone())
+        self.0
+            .borrow()
+            .get(name)
+            .map(|binding| binding.value.clone())
+    }
+
+    /// Whether the variable is defined in this scope as a constant.
+    pub fn is_const(&self, name: &str) -> bool {
+        self.0
+            .borrow()
+            .get(name)
+            .is_some_and(|binding| binding.is_const)
+    }
+
+    /// The names of the variables defined in the scope, in alphabetical order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.0.borrow().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// Scopes are compared by identity rather than by value. Comparing by value
+/// would recurse forever for a function defined in the scope it closes over.
+impl PartialEq for Scope {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
     }
 }
 
-/// Represents a layering of scopes
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Represents a layering of scopes, stored outermost first so the innermost scope
+/// is at the end.
 #[derive(Clone, PartialEq)]
-pub struct MultiScope(LinkedList<Scope>);
+pub struct MultiScope(Vec<Scope>);
 
 impl MultiScope {
     /// Creates a new multi scope with one layer.
     pub fn new() -> Self {
-        let mut list = LinkedList::new();
-        list.push_back(Scope::new());
-        Self(list)
+        Self(vec![Scope::new()])
     }
 
     /// Bubbling up iterator methods. Iterates from inside out (local scope to outer scope)
@@ -63,15 +120,15 @@ impl MultiScope {
         self.0.iter_mut().rev()
     }
 
-    /// Bubbling up iterator methods. Iterates from inside out (local scope to outer scope)
-    pub fn into_iter(self) -> impl Iterator<Item = Scope> {
-        self.0.into_iter().rev()
-    }
-
     /// Get a mutable reference to the innermost (most local) scope
     fn innermost_mut(&mut self) -> &mut Scope {
         // Unwrap is safe because inner list never has 0 elements
-        self.iter_mut().next().unwrap()
+        self.0.last_mut().unwrap()
+    }
+
+    /// Finds the index of the scope `depth` layers out from the innermost one.
+    fn index_of_depth(&self, depth: usize) -> Option<usize> {
+        self.0.len().checked_sub(depth + 1)
     }
 
     /// Removes and returns the innermost scope layer.
@@ -84,11 +141,33 @@ impl MultiScope {
     /// about whether or not we are operating in the local or global scope
     /// to the environment struct, rather than having to implement
     /// a ton of failable methods on multiscope.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{environment::{MultiScope, Scope}, object::LoxObject};
+    ///
+    /// let mut multi_scope = MultiScope::new();
+    /// multi_scope.define("x", LoxObject::Number(0.0));
+    /// let mut inner = Scope::new();
+    /// inner.define("x", LoxObject::Number(1.0));
+    /// multi_scope.push_as_innermost_scope(inner);
+    /// let mut innermost = Scope::new();
+    /// innermost.define("x", LoxObject::Number(2.0));
+    /// multi_scope.push_as_innermost_scope(innermost);
+    ///
+    /// // Scopes come back off in the reverse of the order they went on.
+    /// assert!(multi_scope.pop_innermost_scope().unwrap().get("x") == Some(LoxObject::Number(2.0)));
+    /// assert!(multi_scope.get("x") == Some(LoxObject::Number(1.0)));
+    /// assert!(multi_scope.pop_innermost_scope().unwrap().get("x") == Some(LoxObject::Number(1.0)));
+    /// assert!(multi_scope.get("x") == Some(LoxObject::Number(0.0)));
+    ///
+    /// // The last layer is never popped.
+    /// assert!(multi_scope.pop_innermost_scope().is_none());
+    /// ```
     pub fn pop_innermost_scope(&mut self) -> Option<Scope> {
         if self.0.len() == 1 {
             None
         } else {
-            self.0.pop_back()
+            self.0.pop()
         }
     }
 
@@ -109,13 +188,13 @@ impl MultiScope {
     /// };
     /// ```
     pub fn consume_final_layer(mut self) -> Scope {
-        // We know this unwrap is safe because always has at least one scope.
-        self.0.pop_front().unwrap()
+        // We know this index is safe because always has at least one scope.
+        self.0.swap_remove(0)
     }
 
     /// Add a scope layer to the environment
     pub fn push_as_innermost_scope(&mut self, scope: Scope) {
-        self.0.push_back(scope);
+        self.0.push(scope);
     }
 
     /// Defines a variable in the innermost scope.
@@ -123,6 +202,11 @@ impl MultiScope {
         self.innermost_mut().define(name, value);
     }
 
+    /// Defines a constant in the innermost scope.
+    pub fn define_const(&mut self, name: &str, value: LoxObject) {
+        self.innermost_mut().define_const(name, value);
+    }
+
     /// Tries to assign Lox Object to the variable in the closest scope. Returns
     /// the Some(old_obj) if successful or None if the variables isn't defined in any
     /// of the scopes.
@@ -147,6 +231,63 @@ impl MultiScope {
         }
         None
     }
+
+    /// Gets a variable from the scope `depth` layers out from the innermost one, without
+    /// looking anywhere else. Returns None if the variable isn't defined in that scope.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{environment::{MultiScope, Scope}, object::LoxObject};
+    ///
+    /// let mut multi_scope = MultiScope::new();
+    /// multi_scope.define("a", LoxObject::Number(2.0));
+    /// multi_scope.push_as_innermost_scope(Scope::new());
+    /// multi_scope.define("a", LoxObject::Number(1.0));
+    /// multi_scope.push_as_innermost_scope(Scope::new());
+    /// multi_scope.define("a", LoxObject::Number(0.0));
+    ///
+    /// assert!(multi_scope.get_at(0, "a") == Some(LoxObject::Number(0.0)));
+    /// assert!(multi_scope.get_at(1, "a") == Some(LoxObject::Number(1.0)));
+    /// assert!(multi_scope.get_at(2, "a") == Some(LoxObject::Number(2.0)));
+    /// assert!(multi_scope.get_at(3, "a").is_none());
+    ///
+    /// multi_scope.assign_at(1, "a", LoxObject::Number(10.0));
+    /// assert!(multi_scope.get_at(1, "a") == Some(LoxObject::Number(10.0)));
+    /// assert!(multi_scope.get("a") == Some(LoxObject::Number(0.0)));
+    /// ```
+    pub fn get_at(&self, depth: usize, name: &str) -> Option<LoxObject> {
+        self.index_of_depth(depth)
+            .and_then(|index| self.0[index].get(name))
+    }
+
+    /// Reassigns a variable in the scope `depth` layers out from the innermost one.
+    /// Returns the old value, or None if the variable isn't defined in that scope.
+    pub fn assign_at(&mut self, depth: usize, name: &str, value: LoxObject) -> Option<LoxObject> {
+        self.index_of_depth(depth)
+            .and_then(|index| self.0[index].assign(name, value))
+    }
+
+    /// Whether the variable in the scope `depth` layers out from the innermost one
+    /// is a constant.
+    pub fn is_const_at(&self, depth: usize, name: &str) -> bool {
+        self.index_of_depth(depth)
+            .is_some_and(|index| self.0[index].is_const(name))
+    }
+}
+
+impl Default for MultiScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntoIterator for MultiScope {
+    type Item = Scope;
+    type IntoIter = std::iter::Rev<std::vec::IntoIter<Scope>>;
+
+    /// Bubbling up iterator methods. Iterates from inside out (local scope to outer scope)
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter().rev()
+    }
 }
 
 /// Represents a program execution environment.
@@ -178,6 +319,124 @@ impl Environment {
         new_env
             .global
             .define("print", LoxObject::Function(Box::new(Print {})));
+        new_env
+            .global
+            .define("write", LoxObject::Function(Box::new(PrintNoNewline {})));
+        new_env
+            .global
+            .define("input", LoxObject::Function(Box::new(Input {})));
+        new_env
+            .global
+            .define("len", LoxObject::Function(Box::new(Len {})));
+        new_env
+            .global
+            .define("type", LoxObject::Function(Box::new(Type {})));
+        new_env
+            .global
+            .define("sqrt", LoxObject::Function(Box::new(Sqrt {})));
+        new_env
+            .global
+            .define("floor", LoxObject::Function(Box::new(Floor {})));
+        new_env
+            .global
+            .define("ceil", LoxObject::Function(Box::new(Ceil {})));
+        new_env
+            .global
+            .define("abs", LoxObject::Function(Box::new(Abs {})));
+        new_env
+            .global
+            .define("pow", LoxObject::Function(Box::new(Pow {})));
+        new_env
+            .global
+            .define("to_number", LoxObject::Function(Box::new(ToNumber {})));
+        new_env
+            .global
+            .define("to_string", LoxObject::Function(Box::new(ToStr {})));
+        new_env
+            .global
+            .define("char_at", LoxObject::Function(Box::new(CharAt {})));
+        new_env
+            .global
+            .define("substring", LoxObject::Function(Box::new(Substring {})));
+        new_env
+            .global
+            .define("split", LoxObject::Function(Box::new(Split {})));
+        new_env
+            .global
+            .define("join", LoxObject::Function(Box::new(Join {})));
+        new_env
+            .global
+            .define("map", LoxObject::Function(Box::new(Map {})));
+        new_env
+            .global
+            .define("filter", LoxObject::Function(Box::new(Filter {})));
+        new_env
+            .global
+            .define("reduce", LoxObject::Function(Box::new(Reduce {})));
+        new_env.global.define(
+            "clock_millis",
+            LoxObject::Function(Box::new(ClockMillis {})),
+        );
+        new_env
+            .global
+            .define("sleep", LoxObject::Function(Box::new(Sleep {})));
+        new_env
+            .global
+            .define("random", LoxObject::Function(Box::new(Random {})));
+        new_env
+            .global
+            .define("random_int", LoxObject::Function(Box::new(RandomInt {})));
+        new_env
+            .global
+            .define("seed_random", LoxObject::Function(Box::new(SeedRandom {})));
+        new_env
+            .global
+            .define("assert", LoxObject::Function(Box::new(Assert {})));
+        new_env
+            .global
+            .define("assert_eq", LoxObject::Function(Box::new(AssertEq {})));
+        new_env
+            .global
+            .define("bool", LoxObject::Function(Box::new(Bool {})));
+        new_env
+            .global
+            .define("globals", LoxObject::Function(Box::new(Globals {})));
+        new_env
+            .global
+            .define("locals", LoxObject::Function(Box::new(Locals {})));
+        new_env
+            .global
+            .define("push", LoxObject::Function(Box::new(Push {})));
+        new_env
+            .global
+            .define("pop", LoxObject::Function(Box::new(Pop {})));
+        new_env
+            .global
+            .define("insert", LoxObject::Function(Box::new(Insert {})));
+        new_env
+            .global
+            .define("contains", LoxObject::Function(Box::new(Contains {})));
+        new_env
+            .global
+            .define("index_of", LoxObject::Function(Box::new(IndexOf {})));
+        new_env
+            .global
+            .define("round", LoxObject::Function(Box::new(Round {})));
+        new_env
+            .global
+            .define("to_fixed", LoxObject::Function(Box::new(ToFixed {})));
+        new_env
+            .global
+            .define("min", LoxObject::Function(Box::new(Min {})));
+        new_env
+            .global
+            .define("max", LoxObject::Function(Box::new(Max {})));
+        new_env
+            .global
+            .define("equals", LoxObject::Function(Box::new(Equals {})));
+        new_env
+            .global
+            .define("clone", LoxObject::Function(Box::new(ShallowClone {})));
 
         new_env
     }
@@ -210,6 +469,14 @@ impl Environment {
         }
     }
 
+    /// Returns a copy of the environment with an extra scope layer on top.
+    /// The existing layers are shared with the original environment.
+    pub fn enclosed(&self) -> Self {
+        let mut env = self.clone();
+        env.add_scope_layer();
+        env
+    }
+
     /// Perform some operation inside an extra scope layer. Used for block stmts,
     /// functions, etc.
     pub fn in_new_local_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
@@ -230,19 +497,46 @@ impl Environment {
         }
     }
 
+    /// Define a constant in the most local scope. If no local scope is available,
+    /// defines in the global scope.
+    pub fn define_const(&mut self, name: &str, value: LoxObject) {
+        if let Some(ref mut local_scope) = self.local {
+            local_scope.define_const(name, value);
+        } else {
+            self.global.define_const(name, value);
+        }
+    }
+
+    /// Checks that a declaration isn't about to replace a global constant, which
+    /// would quietly make it assignable again. Locals can't be declared twice in
+    /// the same scope at all, which the resolver already checks.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::lox::run_source;
+    ///
+    /// let errors = run_source("const c = 1;\nvar c = 9;").unwrap_err();
+    /// assert!(errors[0].starts_with("[Line 2, Col 5] Error at 'c': Can't redeclare constant c"));
+    /// let errors = run_source("const f = 1;\nfun f() {}").unwrap_err();
+    /// assert!(errors[0].starts_with("[Line 2, Col 5] Error at 'f': Can't redeclare constant f"));
+    /// assert!(run_source("var v = 1;\nvar v = 2;").is_ok());
+    /// ```
+    pub fn check_redeclaration(&self, name: &Token) -> RuntimeResult<()> {
+        if self.local.is_none() && self.global.is_const(&name.lexeme) {
+            return Err(RuntimeError::new(
+                name.clone(),
+                format!("Can't redeclare constant {}", name.lexeme),
+            ));
+        }
+        Ok(())
+    }
+
     /// Reassign the value of a variable in the environment
     pub fn assign(&mut self, name: Token, value: LoxObject) -> RuntimeResult<()> {
         self.local
             .as_mut()
-            .map(|local_scope| local_scope.assign(&name.lexeme, value.clone()))
-            .flatten()
+            .and_then(|local_scope| local_scope.assign(&name.lexeme, value.clone()))
             .or(self.global.assign(&name.lexeme, value.clone()))
-            .ok_or(RuntimeError::WithMsg(
-                RuntimeErrorCtx {
-                    token: name.clone(),
-                },
-                format!("Undefined variable {}", name.lexeme),
-            ))
+            .ok_or_else(|| undefined_variable(name))
             .map(|_| ())
     }
 
@@ -250,15 +544,94 @@ impl Environment {
     pub fn get(&self, name: Token) -> RuntimeResult<LoxObject> {
         self.local
             .as_ref()
-            .map(|local_scope| local_scope.get(&name.lexeme))
-            .flatten()
+            .and_then(|local_scope| local_scope.get(&name.lexeme))
             .or(self.global.get(&name.lexeme))
-            .ok_or(RuntimeError::WithMsg(
-                RuntimeErrorCtx {
-                    token: name.clone(),
-                },
-                format!("Undefined variable {}", name.lexeme),
-            ))
+            .ok_or_else(|| undefined_variable(name))
+    }
+
+    /// Retrieve a variable from the scope `depth` layers out from the innermost one,
+    /// as worked out by the resolver. A depth of `None` means the variable is global.
+    pub fn get_at(&self, depth: Option<usize>, name: &Token) -> RuntimeResult<LoxObject> {
+        match depth {
+            Some(depth) => self
+                .local
+                .as_ref()
+                .and_then(|local_scope| local_scope.get_at(depth, &name.lexeme)),
+            None => self.global.get(&name.lexeme),
+        }
+        .ok_or_else(|| undefined_variable(name.clone()))
+    }
+
+    /// The names of the global variables, builtins included.
+    pub fn global_names(&self) -> Vec<String> {
+        self.global.names()
+    }
+
+    /// The names of the variables in the innermost scope. At the top level of a
+    /// program that's the global scope.
+    pub fn local_names(&self) -> Vec<String> {
+        match self.local {
+            Some(ref local_scope) => local_scope
+                .iter()
+                .next()
+                .map(Scope::names)
+                .unwrap_or_default(),
+            None => self.global.names(),
+        }
+    }
+
+    /// Reassign a variable in the scope `depth` layers out from the innermost one.
+    /// A depth of `None` means the variable is global. Constants can't be reassigned.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::lox::run_source;
+    ///
+    /// let errors = run_source("const a = 1;\na = 2;").unwrap_err();
+    /// assert!(errors[0].starts_with("[Line 2, Col 1] Error at 'a': Can't assign to constant a"));
+    /// let errors = run_source("{ const b = 1; b++; }").unwrap_err();
+    /// assert!(errors[0].starts_with("[Line 1, Col 16] Error at 'b': Can't assign to constant b"));
+    /// assert!(run_source("var c = 1;\nc = 2;").is_ok());
+    /// ```
+    pub fn assign_at(
+        &mut self,
+        depth: Option<usize>,
+        name: &Token,
+        value: LoxObject,
+    ) -> RuntimeResult<()> {
+        let is_const = match depth {
+            Some(depth) => self
+                .local
+                .as_ref()
+                .is_some_and(|local_scope| local_scope.is_const_at(depth, &name.lexeme)),
+            None => self.global.is_const(&name.lexeme),
+        };
+        if is_const {
+            return Err(RuntimeError::new(
+                name.clone(),
+                format!("Can't assign to constant {}", name.lexeme),
+            ));
+        }
+
+        match depth {
+            Some(depth) => self
+                .local
+                .as_mut()
+                .and_then(|local_scope| local_scope.assign_at(depth, &name.lexeme, value)),
+            None => self.global.assign(&name.lexeme, value),
+        }
+        .ok_or_else(|| undefined_variable(name.clone()))
+        .map(|_| ())
+    }
+}
+
+fn undefined_variable(name: Token) -> RuntimeError {
+    let msg = format!("Undefined variable {}", name.lexeme);
+    RuntimeError::WithMsg(RuntimeErrorCtx { token: name }, msg)
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -267,7 +640,7 @@ impl std::fmt::Display for Environment {
         if let Some(ref local_scope) = self.local {
             write!(f, "Global: {}\nLocal: {}\n", self.global, local_scope)
         } else {
-            write!(f, "Global: {}\n", self.global)
+            writeln!(f, "Global: {}", self.global)
         }
     }
 }
@@ -278,7 +651,7 @@ impl std::fmt::Display for MultiScope {
         for scope in self.0.iter() {
             buffer.push_str(&format!("{},\n", scope));
         }
-        buffer.push_str("]");
+        buffer.push(']');
         write!(f, "{}", buffer)
     }
 }
@@ -286,10 +659,10 @@ impl std::fmt::Display for MultiScope {
 impl std::fmt::Display for Scope {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut buffer = String::from("{\n");
-        for (key, value) in self.0.iter() {
-            buffer.push_str(&format!("   {} = {},\n", key, value));
+        for (key, binding) in self.0.borrow().iter() {
+            buffer.push_str(&format!("   {} = {},\n", key, binding.value));
         }
-        buffer.push_str("}");
+        buffer.push('}');
         write!(f, "{}", buffer)
     }
 }
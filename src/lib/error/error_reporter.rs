@@ -1,20 +1,104 @@
 /// The error reporter is a very simple interface for reporting errors
 /// via stdout and recording whether or not an error occurred.
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ErrorReporter {
     pub had_error: bool,
+
+    /// Every error reported so far, in the order they were reported.
+    pub messages: Vec<String>,
+
+    /// Whether there's been a warning, which (unlike an error) doesn't stop anything.
+    pub had_warning: bool,
+
+    /// Every warning so far, kept apart from the errors.
+    pub warnings: Vec<String>,
+
+    /// The file the errors are in, which replaces the `Line` in each message's location.
+    pub file_name: Option<String>,
+
+    /// Whether errors (and warnings) are printed to stderr as they're reported. Turned off when
+    /// whoever owns the reporter prints the messages somewhere else.
+    pub print_errors: bool,
 }
 
 impl ErrorReporter {
     /// Basic constructor. Creates a new error reporter with had_error set to false.
     pub fn new() -> Self {
-        Self { had_error: false }
+        Self {
+            had_error: false,
+            messages: vec![],
+            had_warning: false,
+            warnings: vec![],
+            file_name: None,
+            print_errors: true,
+        }
+    }
+
+    /// Creates an error reporter which labels each error with the file it's in.
+    pub fn for_file(file_name: impl Into<String>) -> Self {
+        Self {
+            file_name: Some(file_name.into()),
+            ..Self::new()
+        }
     }
 
     /// Report any error that implements std::fmt::Display. The error
-    /// will be print to the console and had_error will be set to true.
+    /// will be print to the console (unless `print_errors` is off) and had_error will be set to true.
     pub fn error(&mut self, error: impl std::fmt::Display) {
-        eprintln!("{}", error);
+        let msg = self.format(error);
+        if self.print_errors {
+            eprintln!("{}", msg);
+        }
+        self.messages.push(msg);
         self.had_error = true;
     }
+
+    /// Report a warning, which is printed the same way as an error but only
+    /// sets had_warning.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::error::error_reporter::ErrorReporter;
+    ///
+    /// let mut error_reporter = ErrorReporter::new();
+    /// error_reporter.warning("[Line 1, Col 1] Warning at 'x': Something looks off");
+    /// assert!(error_reporter.had_warning);
+    /// assert!(!error_reporter.had_error);
+    /// assert!(error_reporter.messages.is_empty());
+    /// assert_eq!(error_reporter.warnings.len(), 1);
+    /// ```
+    pub fn warning(&mut self, warning: impl std::fmt::Display) {
+        let msg = self.format(warning);
+        if self.print_errors {
+            eprintln!("{}", msg);
+        }
+        self.warnings.push(msg);
+        self.had_warning = true;
+    }
+
+    /// Formats an error the way it gets reported, labelled with the file it's in.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::error::error_reporter::ErrorReporter;
+    ///
+    /// let error = "[Line 12, Col 5] Error at 'x': Undefined variable x";
+    /// assert_eq!(ErrorReporter::new().format(error), error);
+    /// assert_eq!(
+    ///     ErrorReporter::for_file("file.lox").format(error),
+    ///     "[file.lox:12, Col 5] Error at 'x': Undefined variable x"
+    /// );
+    /// ```
+    pub fn format(&self, error: impl std::fmt::Display) -> String {
+        let msg = error.to_string();
+        let file_name = match &self.file_name {
+            Some(file_name) => file_name,
+            None => return msg,
+        };
+
+        // Every located error starts with `[Line N, Col M]`, so the file name
+        // goes where the `Line` was.
+        match msg.strip_prefix("[Line ") {
+            Some(rest) => format!("[{}:{}", file_name, rest),
+            None => format!("{}: {}", file_name, msg),
+        }
+    }
 }
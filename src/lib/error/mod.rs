@@ -1,4 +1,6 @@
 pub mod error_reporter;
 pub mod parse_error;
+pub mod resolve_error;
 pub mod runtime_error;
 pub mod scan_error;
+pub mod warning;
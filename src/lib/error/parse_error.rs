@@ -13,8 +13,38 @@ pub enum ParseError {
     #[error("{0}: Expected '{1}'")]
     ExpectedDifferentToken(ParseErrorCtx, TokenType),
 
+    #[error("{0}: Expected a file name string after 'import'")]
+    ExpectedImportPath(ParseErrorCtx),
+
+    #[error("{0}: A constant must be given a value")]
+    ConstWithoutInitializer(ParseErrorCtx),
+
+    #[error("{0}: Expected '}}' after the expression embedded in a string")]
+    UnterminatedInterpolation(ParseErrorCtx),
+
+    #[error("{0}: Comparisons can't be chained, use parentheses or 'and' instead (like 'a < b and b < c')")]
+    ChainedComparison(ParseErrorCtx),
+
     #[error("{0}: Cannot have more that 255 arguments for a function (Seriously chill)")]
     TooManyFunctionArguments(ParseErrorCtx),
+
+    #[error("{0}: Cannot use '{1}' outside of a loop")]
+    OutsideOfLoop(ParseErrorCtx, TokenType),
+
+    #[error("{0}: Only loops can be labelled")]
+    LabelOnNonLoop(ParseErrorCtx),
+
+    #[error("{0}: No enclosing loop is labelled '{1}'")]
+    UndefinedLabel(ParseErrorCtx, String),
+
+    #[error("{0}: Cannot use 'return' outside of a function")]
+    ReturnOutsideFunction(ParseErrorCtx),
+
+    #[error("{0}: Parameters without a default value must come before those with one")]
+    RequiredParamAfterDefault(ParseErrorCtx),
+
+    #[error("{0}: A rest parameter must be the last parameter")]
+    RestParamNotLast(ParseErrorCtx),
 }
 
 #[derive(Debug)]
@@ -30,10 +60,19 @@ impl From<Token> for ParseErrorCtx {
 
 impl std::fmt::Display for ParseErrorCtx {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "[Line {}] Error at '{}'",
-            self.token.line, self.token.lexeme
-        )
+        // The end of file token has no lexeme to point at.
+        if self.token.token_type == TokenType::Eof {
+            write!(
+                f,
+                "[Line {}, Col {}] Error at end",
+                self.token.line, self.token.column
+            )
+        } else {
+            write!(
+                f,
+                "[Line {}, Col {}] Error at '{}'",
+                self.token.line, self.token.column, self.token.lexeme
+            )
+        }
     }
 }
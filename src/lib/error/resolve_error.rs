@@ -0,0 +1,14 @@
+use thiserror::Error;
+
+use super::parse_error::ParseErrorCtx;
+
+/// An enum to represent the static errors the resolver finds, after parsing but
+/// before anything runs.
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("{0}: Can't read a local variable in its own initializer")]
+    ReadInOwnInitializer(ParseErrorCtx),
+
+    #[error("{0}: Already a variable with this name in this scope")]
+    AlreadyDeclared(ParseErrorCtx),
+}
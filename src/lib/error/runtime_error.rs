@@ -1,6 +1,8 @@
 use thiserror::Error;
 
-use crate::token::Token;
+use crate::{
+    error::error_reporter::ErrorReporter, interpreter::Source, object::LoxObject, token::Token,
+};
 
 /// An enum to represent all possible errors encountered while scanning/lexing
 #[derive(Error, Debug, Clone)]
@@ -8,12 +10,136 @@ pub enum RuntimeError {
     /// For the moment, I don't want to try to enumerate all the errors so I'll just pass a message.
     #[error("{0}: {1}")]
     WithMsg(RuntimeErrorCtx, String),
+
+    /// Native functions don't know where they were called from, so they raise this instead
+    /// and the interpreter attaches the location of the call.
+    #[error("{0}")]
+    Native(String),
+
+    /// A value raised by a `throw` statement. A `catch` gets the value itself
+    /// rather than a message.
+    #[error("{0}: {1}")]
+    Thrown(RuntimeErrorCtx, Box<LoxObject>),
+
+    /// A located error along with a snippet of the source line it came from,
+    /// with a caret pointing at the offending token.
+    #[error("{0}\n{1}")]
+    StringError(Box<RuntimeError>, String),
+
+    /// An error labelled with the name of the file it happened in.
+    #[error("{}", ErrorReporter::for_file(.1).format(.0))]
+    InFile(Box<RuntimeError>, String),
 }
 
 impl RuntimeError {
     pub fn new(token: Token, msg: impl std::fmt::Display) -> Self {
         Self::WithMsg(RuntimeErrorCtx { token }, msg.to_string())
     }
+
+    /// Constructs an error from inside a native function.
+    pub fn native(msg: impl std::fmt::Display) -> Self {
+        Self::Native(msg.to_string())
+    }
+
+    /// The value a `catch` binds for this error. That's whatever was thrown, or for
+    /// errors raised by the interpreter itself, the message without where it happened.
+    pub fn caught_value(&self) -> LoxObject {
+        match self {
+            Self::WithMsg(_, msg) | Self::Native(msg) => LoxObject::String(msg.clone()),
+            Self::Thrown(_, value) => (**value).clone(),
+            Self::StringError(error, _) | Self::InFile(error, _) => error.caught_value(),
+        }
+    }
+
+    /// Gives an error raised by a native function the location it was called from.
+    /// Errors which already have a location are left alone.
+    pub fn at_call_site(self, token: &Token) -> Self {
+        match self {
+            Self::Native(msg) => Self::new(token.clone(), msg),
+            located => located,
+        }
+    }
+
+    /// Labels the error with the source it happened in: the line it's on (see
+    /// `with_source`) and the name of the file, if there is one. Errors that
+    /// already have a source (from inside a function declared somewhere else)
+    /// are left alone.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// // The error is in the function declared on an earlier REPL line, so that's the
+    /// // line shown, not the call.
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.run_repl_line("fun f() { return missing; }").unwrap();
+    /// let errors = interpreter.run_repl_line("f();").unwrap_err();
+    /// let expected = [
+    ///     "[Line 1, Col 18] Error at 'missing': Undefined variable missing",
+    ///     "  |",
+    ///     "1 | fun f() { return missing; }",
+    ///     "  |                  ^^^^^^^",
+    /// ];
+    /// assert_eq!(errors[0], expected.join("\n"));
+    /// ```
+    pub fn in_source(self, source: &Source) -> Self {
+        if matches!(self, Self::StringError(..) | Self::InFile(..)) {
+            return self;
+        }
+
+        let error = self.with_source(&source.text);
+        match &source.name {
+            Some(name) => Self::InFile(Box::new(error), name.clone()),
+            None => error,
+        }
+    }
+
+    /// Attaches the line of source code the error happened on, so it gets printed
+    /// underneath the error message. Errors without a location (or whose line can't
+    /// be found in `source`) are left alone.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::lox::run_source;
+    ///
+    /// let errors = run_source("var a = 1;\nprint(a + missing);").unwrap_err();
+    /// let expected = [
+    ///     "[Line 2, Col 11] Error at 'missing': Undefined variable missing",
+    ///     "  |",
+    ///     "2 | print(a + missing);",
+    ///     "  |           ^^^^^^^",
+    /// ];
+    /// assert_eq!(errors[0], expected.join("\n"));
+    /// ```
+    pub fn with_source(self, source: &str) -> Self {
+        let token = match &self {
+            Self::WithMsg(RuntimeErrorCtx { token }, _)
+            | Self::Thrown(RuntimeErrorCtx { token }, _) => token,
+            _ => return self,
+        };
+        let text = match token
+            .line
+            .checked_sub(1)
+            .and_then(|i| source.lines().nth(i))
+        {
+            Some(text) => text,
+            None => return self,
+        };
+
+        // Keep any tabs before the token so the caret lines up however wide they render.
+        let padding: String = text
+            .chars()
+            .take(token.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let remaining = text.chars().count().saturating_sub(padding.chars().count());
+        let carets = "^".repeat(token.lexeme.chars().count().clamp(1, remaining.max(1)));
+
+        let gutter = " ".repeat(token.line.to_string().len());
+        let snippet = format!(
+            "{} |\n{} | {}\n{} | {}{}",
+            gutter, token.line, text, gutter, padding, carets
+        );
+        Self::StringError(Box::new(self), snippet)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,8 +151,8 @@ impl std::fmt::Display for RuntimeErrorCtx {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "[Line {}] Error at '{}'",
-            self.token.line, self.token.lexeme
+            "[Line {}, Col {}] Error at '{}'",
+            self.token.line, self.token.column, self.token.lexeme
         )
     }
 }
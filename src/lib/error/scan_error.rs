@@ -8,21 +8,25 @@ pub enum ScanError {
 
     #[error("{0}: Unterminated String")]
     UnterminatedString(ScanErrorCtx),
+
+    #[error("{0}: Invalid Escape Sequence")]
+    InvalidEscapeSequence(ScanErrorCtx),
+
+    #[error("{0}: Unterminated Block Comment")]
+    UnterminatedBlockComment(ScanErrorCtx),
+
+    #[error("{0}: Invalid Number '{1}'")]
+    InvalidNumber(ScanErrorCtx, String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ScanErrorCtx {
     pub line: usize,
-}
-
-impl From<usize> for ScanErrorCtx {
-    fn from(line: usize) -> Self {
-        Self { line }
-    }
+    pub column: usize,
 }
 
 impl std::fmt::Display for ScanErrorCtx {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "[Line {}]", self.line)
+        write!(f, "[Line {}, Col {}]", self.line, self.column)
     }
 }
@@ -0,0 +1,35 @@
+use thiserror::Error;
+
+use crate::token::Token;
+
+/// An enum to represent the mistakes which are worth pointing out, but don't stop
+/// the program from running.
+#[derive(Error, Debug)]
+pub enum Warning {
+    #[error("{0}: Code after '{1}' can never run")]
+    UnreachableCode(WarningCtx, String),
+
+    #[error("{0}: Local variable '{1}' is never read")]
+    UnusedVariable(WarningCtx, String),
+}
+
+#[derive(Debug)]
+pub struct WarningCtx {
+    token: Token,
+}
+
+impl From<Token> for WarningCtx {
+    fn from(token: Token) -> Self {
+        Self { token }
+    }
+}
+
+impl std::fmt::Display for WarningCtx {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "[Line {}, Col {}] Warning at '{}'",
+            self.token.line, self.token.column, self.token.lexeme
+        )
+    }
+}
@@ -1,8 +1,10 @@
+use std::{cell::RefCell, rc::Rc};
+
 use crate::{
     callable::LoxCallable,
-    environment::{Environment, Scope},
-    grammar::{FunctionDeclarationStmt, Stmt},
-    interpreter::Interpreter,
+    environment::Environment,
+    grammar::{FunctionDeclarationStmt, Param, Stmt},
+    interpreter::{ControlFlow, Interpreter, RuntimeResult, Source},
     object::LoxObject,
     token::Token,
 };
@@ -13,64 +15,161 @@ pub struct LoxFunction {
     /// The token of the function name from the function declaration
     name: Token,
 
-    /// The tokens from the function declaration describing the parameters
-    /// of the function.
-    params: Vec<Token>,
+    /// The parameters from the function declaration, along with any default values.
+    /// Shared between every copy of the function, since they never change.
+    params: Rc<[Param]>,
 
     /// The parsed list of statements from the body of the function declaration.
-    body: Vec<Stmt>,
-
-    /// This is a basically a place for the function to store private state between calls.
-    /// Lox functions are closures, and when they are declared, if they reference 
-    /// a variable from an enclosing scope, they should basically get a copy of that 
-    /// variable as private state.
-    /// 
-    /// The book does an entire chapter implementing variable resolving and binding 
-    /// as a separate pass. 
-    state: Scope
+    /// Shared like the parameters, so calling the function doesn't copy its body.
+    body: Rc<[Stmt]>,
+
+    /// The environment the function was declared in. Lox functions are closures, so
+    /// when they reference a variable from an enclosing scope, they should see that
+    /// variable even after the enclosing scope has finished executing.
+    ///
+    /// Scopes are shared rather than copied, so the function sees (and can update)
+    /// the same variables as the code around its declaration.
+    closure: Environment,
+
+    /// The source code the function was declared in. Errors inside the function are
+    /// labelled with it, even when it's called from another file (or REPL line).
+    source: Source,
 }
 
 impl LoxFunction {
-    /// Construct a function object from the function declaration statement parsed by the parser.
-    pub fn from(FunctionDeclarationStmt { name, params, body }: FunctionDeclarationStmt) -> Self {
-        Self { name, params, body, state: Scope::new() }
+    /// Construct a function object from the function declaration statement parsed by the parser,
+    /// closing over the environment it was declared in.
+    pub fn new(
+        FunctionDeclarationStmt { name, params, body }: &FunctionDeclarationStmt,
+        closure: Environment,
+        source: Source,
+    ) -> Self {
+        Self {
+            name: name.clone(),
+            params: params.as_slice().into(),
+            body: body.as_slice().into(),
+            closure,
+            source,
+        }
+    }
+
+    /// Whether two function objects are the same function, declared by the same
+    /// declaration and closing over the same environment.
+    pub fn is_same(&self, other: &LoxFunction) -> bool {
+        Rc::ptr_eq(&self.body, &other.body) && self.closure == other.closure
+    }
+
+    /// Creates a copy of a method whose closure has `this` bound to the given instance.
+    pub fn bind(&self, instance: LoxObject) -> Self {
+        let mut closure = self.closure.enclosed();
+        closure.define("this", instance);
+        Self {
+            closure,
+            ..self.clone()
+        }
     }
 }
 
 impl LoxCallable for LoxFunction {
-    /// Returns the number of parameters the function expects
+    /// Returns the number of parameters the function expects, not counting
+    /// the ones with a default value or a rest parameter.
     fn arity(&self) -> usize {
-        self.params.len()
+        self.params
+            .iter()
+            .filter(|param| param.default.is_none() && !param.is_rest)
+            .count()
+    }
+
+    /// A rest parameter takes any number of extra arguments, so there's no limit.
+    fn max_arity(&self) -> Option<usize> {
+        match self.params.last() {
+            Some(param) if param.is_rest => None,
+            _ => Some(self.params.len()),
+        }
+    }
+
+    /// Functions declared in Lox are called by their name.
+    fn name(&self) -> String {
+        self.name.lexeme.clone()
+    }
+
+    fn as_function(&self) -> Option<&LoxFunction> {
+        Some(self)
     }
 
-    /// Calls the function
+    /// Calls the function. The body is executed in the environment the function
+    /// was declared in, not the environment it's called from.
+    ///
+    /// When the function returns a call to itself (a tail call), the body is just run
+    /// again with the new arguments, so deep tail recursion doesn't overflow the stack.
     fn call(
         &self,
         interpreter: &mut Interpreter,
         exec_env: &mut Environment,
-        args: Vec<LoxObject>,
-    ) -> LoxObject {
-        // In a new scope
-        exec_env.in_new_local_scope(|e| {
+        mut args: Vec<LoxObject>,
+    ) -> RuntimeResult<LoxObject> {
+        loop {
+            let control_flow = self
+                .call_once(interpreter, &args)
+                .map_err(|e| e.in_source(&self.source))?;
+            match control_flow {
+                ControlFlow::TailCall(tail_call) if tail_call.function.is_same(self) => {
+                    args = tail_call.args;
+                }
+                ControlFlow::TailCall(tail_call) => {
+                    return interpreter.finish_tail_call(*tail_call, exec_env)
+                }
+                ControlFlow::Return(value) => return Ok(value),
+                _ => return Ok(LoxObject::Nil),
+            }
+        }
+    }
+}
+
+impl LoxFunction {
+    /// Runs the body of the function once with the given arguments, returning how it finished.
+    fn call_once(
+        &self,
+        interpreter: &mut Interpreter,
+        args: &[LoxObject],
+    ) -> RuntimeResult<ControlFlow> {
+        // In a new scope on top of the captured environment
+        let mut closure = self.closure.clone();
+        closure.in_new_local_scope(|e| {
             // Define all the arguments of the function as local
-            // variables
+            // variables. Any left out are filled in from their defaults, which
+            // are evaluated now so they can see the parameters before them.
+            // A rest parameter gathers up whatever arguments are left over.
             for (i, param) in self.params.iter().enumerate() {
-                e.define(&param.lexeme, args[i].clone());
+                if param.is_rest {
+                    let rest = args.get(i..).unwrap_or_default().to_vec();
+                    e.define(
+                        &param.name.lexeme,
+                        LoxObject::Array(Rc::new(RefCell::new(rest))),
+                    );
+                    continue;
+                }
+
+                let value = match (args.get(i), &param.default) {
+                    (Some(arg), _) => arg.clone(),
+                    (None, Some(default)) => interpreter.evaluate(default, e)?,
+                    (None, None) => LoxObject::Nil,
+                };
+                e.define(&param.name.lexeme, value);
             }
 
             // Execute each statement in the body of the function
             // If one of them returns something (return stmt),
-            // break early.
-            let mut return_val = LoxObject::Nil;
-            for stmt in self.body.clone().into_iter() {
-                if let Some(val) = interpreter.execute(stmt, e) {
-                    return_val = val;
-                    break;
+            // break early. If one of them errors, the whole call fails.
+            for stmt in self.body.iter() {
+                match interpreter.execute(stmt, e)? {
+                    ControlFlow::Normal => {}
+                    control_flow => return Ok(control_flow),
                 }
             }
 
-            // Return the result of the function
-            return_val
+            // Falling off the end of the function returns nil
+            Ok(ControlFlow::Normal)
         })
     }
 }
@@ -1,21 +1,35 @@
-use crate::token::Token;
+use std::fmt;
+
+use crate::token::{Token, TokenType};
 
 /// Represents the grammar for expressions in Lox.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Binary(BinaryExpr),
     Grouping(GroupingExpr),
     Literal(LiteralExpr),
     Unary(UnaryExpr),
+    IncDec(IncDecExpr),
     Variable(VariableExpr),
     Assignment(AssignmentExpr),
     Logical(BinaryExpr),
+    Ternary(TernaryExpr),
+    Interpolation(InterpolationExpr),
     Call(CallExpr),
+    Get(GetExpr),
+    Set(SetExpr),
+    This(ThisExpr),
+    Array(ArrayExpr),
+    Index(IndexExpr),
+    IndexSet(IndexSetExpr),
+    Slice(SliceExpr),
 }
 
 /// Represents a Binary Expression.
 /// (Two expressions with an operator in the middle)
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinaryExpr {
     pub lhs: Box<Expr>,
     pub operator: Token,
@@ -25,27 +39,49 @@ pub struct BinaryExpr {
 /// Represents a Unary Expression.
 /// (An operator on the left and an expression to the right, i.e !some_func() or -7)
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnaryExpr {
     pub operator: Token,
     pub rhs: Box<Expr>,
 }
 
+/// Represents incrementing or decrementing a variable, i.e. `++i` or `i--`.
+/// The prefix form evaluates to the new value, and the postfix form to the old one.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IncDecExpr {
+    pub variable: Token,
+    pub operator: Token,
+    pub is_prefix: bool,
+
+    /// How many scopes out the variable lives. Filled in by the resolver, and
+    /// left as `None` for globals.
+    pub depth: Option<usize>,
+}
+
 /// Represents an expression enclosed in parentheses.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GroupingExpr {
     pub expr: Box<Expr>,
 }
 
 /// Represents a literal value, like a number or string.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LiteralExpr {
     pub token: Token,
 }
 
 /// Represents a single variable.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableExpr {
-    pub name: Token
+    pub name: Token,
+
+    /// How many scopes out from the current one the variable was declared in.
+    /// Filled in by the resolver, and left as `None` for globals.
+    pub depth: Option<usize>,
 }
 
 /// Represents variable assignment
@@ -53,60 +89,264 @@ pub struct VariableExpr {
 /// Thus the expression `name = "Ben"` actually evaluates
 /// to the string "Ben"
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AssignmentExpr {
     pub variable: Token,
     pub expr: Box<Expr>,
+
+    /// How many scopes out the variable being assigned to lives. Filled in by
+    /// the resolver, and left as `None` for globals.
+    pub depth: Option<usize>,
+}
+
+/// Represents a conditional expression. Only the branch picked by the
+/// condition is evaluated.
+/// For example: `is_even ? "even" : "odd"`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TernaryExpr {
+    pub condition: Box<Expr>,
+    pub then_expr: Box<Expr>,
+    pub else_expr: Box<Expr>,
+}
+
+/// Represents a string with embedded expressions. Evaluates to the string form of
+/// each part joined together, where the parts alternate between string literals
+/// and the embedded expressions (starting and ending with a string literal).
+/// For example: `"Hello, ${name}!"`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterpolationExpr {
+    pub parts: Vec<Expr>,
 }
 
 /// Represents a function call (or anything callable like a method)
 /// For example: `clock()`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallExpr {
     pub callee: Box<Expr>,
     pub closing_paren: Token,
     pub args: Vec<Expr>,
 }
 
+/// Represents accessing a property on an instance.
+/// For example: `person.name`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+/// Represents assigning to a property on an instance.
+/// Like variable assignment, this is an expression which
+/// evaluates to the assigned value.
+/// For example: `person.name = "Ben"`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+/// Represents the `this` keyword inside a method, which refers
+/// to the instance the method was accessed on.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThisExpr {
+    pub keyword: Token,
+
+    /// How many scopes out `this` was bound. Filled in by the resolver.
+    pub depth: Option<usize>,
+}
+
+/// Represents an array literal.
+/// For example: `[1, 2, 3]`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArrayExpr {
+    pub elements: Vec<Expr>,
+}
+
+/// Represents indexing into an array.
+/// For example: `arr[0]`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexExpr {
+    pub object: Box<Expr>,
+    pub closing_bracket: Token,
+    pub index: Box<Expr>,
+}
+
+/// Represents taking a slice of an array or string, where either bound can be left out.
+/// For example: `arr[1:3]` or `s[:2]`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SliceExpr {
+    pub object: Box<Expr>,
+    pub closing_bracket: Token,
+    pub start: Option<Box<Expr>>,
+    pub end: Option<Box<Expr>>,
+}
+
+/// Represents assigning to an index of an array. Like variable assignment,
+/// this is an expression which evaluates to the assigned value.
+/// For example: `arr[0] = 5`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexSetExpr {
+    pub object: Box<Expr>,
+    pub closing_bracket: Token,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+}
+
 /// Represents the grammar for statements in Lox.
+/// With the `serde` feature, a parsed program can be saved and loaded again.
+/// # Example
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// use rust_lox_impl::{grammar::Stmt, parser::parse, scanner::scan};
+///
+/// let (tokens, _) = scan("var a = [1, 2]; fun f(x = 1) { return a[0] + x; }");
+/// let (stmts, _) = parse(tokens);
+/// let json = serde_json::to_string(&stmts).unwrap();
+/// let reloaded: Vec<Stmt> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(reloaded, stmts);
+/// # }
+/// ```
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     VariableDeclaration(VariableDeclarationStmt),
     Expression(ExpressionStmt),
     While(WhileStmt),
+    DoWhile(DoWhileStmt),
+    ForEach(ForEachStmt),
+    Repeat(RepeatStmt),
     FunctionDeclaration(FunctionDeclarationStmt),
+    ClassDeclaration(ClassDeclarationStmt),
     Block(BlockStmt),
     If(IfStmt),
     Return(ReturnStmt),
+    Break(BreakStmt),
+    Continue(ContinueStmt),
+    Try(TryStmt),
+    Throw(ThrowStmt),
+    Import(ImportStmt),
 }
 
 /// Represents a while loop.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WhileStmt {
     pub condition: Expr,
     pub body: Box<Stmt>,
+
+    /// Only set when a for loop is desugared into a while loop. It's kept separate
+    /// from the body so that it still runs when the body hits a `continue`.
+    pub increment: Option<Expr>,
+
+    /// The name given to the loop, like `outer: while (...)`, so a `break` or
+    /// `continue` in a nested loop can refer to it. The same goes for the other loops.
+    pub label: Option<Token>,
+}
+
+/// Represents a do while loop, which always runs its body at least once.
+/// `do { i = i + 1; } while (i < 10);`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoWhileStmt {
+    pub body: Box<Stmt>,
+    pub condition: Expr,
+    pub label: Option<Token>,
+}
+
+/// Represents a loop over the elements of an array. The variable is bound
+/// in a fresh scope for each element.
+/// `foreach (x in [1, 2, 3]) { print(x); }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForEachStmt {
+    pub variable: Token,
+    pub in_keyword: Token,
+    pub iterable: Expr,
+    pub body: Box<Stmt>,
+    pub label: Option<Token>,
+}
+
+/// Represents a loop which runs its body a set number of times. The count is only
+/// evaluated once, before the first iteration.
+/// `repeat (3) { print("hi"); }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RepeatStmt {
+    pub keyword: Token,
+    pub count: Expr,
+    pub body: Box<Stmt>,
+    pub label: Option<Token>,
 }
 
 /// Represents variable declaration
-/// `var a = true;`
+/// `var a = true;` or `const b = 2;`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableDeclarationStmt {
     pub name: Token,
     pub initializer: Option<Expr>,
+
+    /// Declared with `const`, so it can't be reassigned. Always has an initializer.
+    pub is_const: bool,
 }
 
 /// Represents a function definition.
 /// `fun show_name() { print "Ben"; }`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionDeclarationStmt {
     pub name: Token,
-    pub params: Vec<Token>,
+    pub params: Vec<Param>,
     pub body: Vec<Stmt>,
 }
 
+/// Represents a single parameter in a function declaration, with the
+/// default value to use if the caller leaves it out.
+/// `fun greet(name, greeting = "Hello") { ... }`
+/// A rest parameter collects any extra arguments into an array.
+/// `fun sum(...nums) { ... }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Param {
+    pub name: Token,
+    pub default: Option<Expr>,
+    pub is_rest: bool,
+}
+
+/// Represents a class definition.
+/// `class Person { greet() { print("Hi"); } }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassDeclarationStmt {
+    pub name: Token,
+    pub methods: Vec<FunctionDeclarationStmt>,
+
+    /// Methods declared without a parameter list, like `area { ... }`, which run
+    /// when the property is accessed rather than when it's called.
+    pub getters: Vec<FunctionDeclarationStmt>,
+
+    /// Methods declared with a `class` prefix, like `class make() { ... }`, which
+    /// are called on the class itself rather than on an instance.
+    pub class_methods: Vec<FunctionDeclarationStmt>,
+}
+
 /// Represents an expression statement (an expression followed by a semi colon).
 /// The most common of these is a single function call
 /// `doTheThing();`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExpressionStmt {
     pub expr: Expr,
 }
@@ -114,12 +354,65 @@ pub struct ExpressionStmt {
 /// Represents some code between braces
 /// `{ ... some code ... }`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockStmt {
     pub body: Vec<Stmt>,
 }
 
-/// Represents an if statement.
+/// Represents a try statement. If a runtime error happens in the body, the catch
+/// body runs instead with the error message bound to the catch variable.
+/// `try { risky(); } catch (e) { print(e); }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TryStmt {
+    pub body: BlockStmt,
+    pub catch_variable: Token,
+    pub catch_body: BlockStmt,
+}
+
+/// Represents a throw statement, which raises a runtime error carrying a value.
+/// `throw "Something went wrong";`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThrowStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+/// Represents an import statement, which runs another Lox file so its declarations
+/// end up in the global scope. The path is relative to the importing file.
+/// `import "shapes.lox";`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportStmt {
+    pub keyword: Token,
+    pub path: String,
+}
+
+/// Represents an if statement. Like in C and Java, an `else` belongs to the
+/// nearest `if` that doesn't have one yet (the "dangling else").
+/// # Example
+/// ```
+/// use rust_lox_impl::{grammar::{IfStmt, Stmt}, parser::parse, scanner::scan};
+///
+/// let (tokens, _) = scan("if (a) if (b) x; else y;");
+/// let (stmts, _) = parse(tokens);
+/// let Stmt::If(IfStmt { then_branch, else_branch: None, .. }) = &stmts[0] else {
+///     panic!("The outer if shouldn't have an else");
+/// };
+/// assert!(matches!(**then_branch, Stmt::If(IfStmt { else_branch: Some(_), .. })));
+///
+/// // Printing an outer else keeps it on the outer if, by wrapping the inner one in a block.
+/// let (tokens, _) = scan("if (a) { if (b) x; } else y;");
+/// let (stmts, _) = parse(tokens);
+/// let Stmt::If(outer) = &stmts[0] else { unreachable!() };
+/// let Stmt::Block(block) = &*outer.then_branch else { unreachable!() };
+/// let then_branch = Box::new(block.body[0].clone());
+/// let unbraced = Stmt::If(IfStmt { then_branch, ..outer.clone() });
+/// assert_eq!(unbraced.to_string(), "if (a) {\n    if (b) x;\n} else y;");
+/// ```
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfStmt {
     pub condition: Expr,
     pub then_branch: Box<Stmt>,
@@ -128,7 +421,530 @@ pub struct IfStmt {
 
 /// Represents a return statement.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReturnStmt {
     pub return_keyword: Token,
     pub value: Option<Expr>,
 }
+
+/// Represents a break statement, optionally naming the loop to break out of.
+/// `break;` or `break outer;`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BreakStmt {
+    pub keyword: Token,
+    pub label: Option<Token>,
+}
+
+/// Represents a continue statement, optionally naming the loop to continue.
+/// `continue;` or `continue outer;`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContinueStmt {
+    pub keyword: Token,
+    pub label: Option<Token>,
+}
+
+/// How tightly each kind of expression binds, loosest first. Used to put back
+/// only the parentheses that are needed when printing an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Assignment,
+    Ternary,
+    Coalesce,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Term,
+    Factor,
+    Power,
+    Unary,
+    Postfix,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter level, for the right hand side of left associative operators.
+    fn next(self) -> Self {
+        match self {
+            Precedence::Assignment => Precedence::Ternary,
+            Precedence::Ternary => Precedence::Coalesce,
+            Precedence::Coalesce => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Power,
+            Precedence::Power => Precedence::Unary,
+            Precedence::Unary => Precedence::Postfix,
+            Precedence::Postfix => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+impl Expr {
+    fn precedence(&self) -> Precedence {
+        match self {
+            Expr::Assignment(_) | Expr::Set(_) | Expr::IndexSet(_) => Precedence::Assignment,
+            Expr::Ternary(_) => Precedence::Ternary,
+            Expr::Binary(BinaryExpr { operator, .. })
+            | Expr::Logical(BinaryExpr { operator, .. }) => match operator.token_type {
+                TokenType::QuestionQuestion => Precedence::Coalesce,
+                TokenType::Or => Precedence::Or,
+                TokenType::And => Precedence::And,
+                TokenType::EqualEqual | TokenType::BangEqual => Precedence::Equality,
+                TokenType::Pipe => Precedence::BitOr,
+                TokenType::Caret => Precedence::BitXor,
+                TokenType::Ampersand => Precedence::BitAnd,
+                TokenType::LessLess | TokenType::GreaterGreater => Precedence::Shift,
+                TokenType::Plus | TokenType::Minus => Precedence::Term,
+                TokenType::Star | TokenType::Slash | TokenType::Percent => Precedence::Factor,
+                TokenType::StarStar => Precedence::Power,
+                _ => Precedence::Comparison,
+            },
+            Expr::Unary(_) => Precedence::Unary,
+            Expr::IncDec(IncDecExpr { is_prefix, .. }) => {
+                if *is_prefix {
+                    Precedence::Unary
+                } else {
+                    Precedence::Postfix
+                }
+            }
+            Expr::Call(_) | Expr::Get(_) | Expr::Index(_) | Expr::Slice(_) => Precedence::Call,
+            Expr::Grouping(_)
+            | Expr::Interpolation(_)
+            | Expr::Literal(_)
+            | Expr::Variable(_)
+            | Expr::This(_)
+            | Expr::Array(_) => Precedence::Primary,
+        }
+    }
+
+    /// Writes the expression, wrapping it in parentheses if it binds looser than
+    /// the position it's being written in allows.
+    fn fmt_at(&self, f: &mut fmt::Formatter, min: Precedence) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+/// Writes a comma separated list of expressions.
+fn fmt_list(f: &mut fmt::Formatter, exprs: &[Expr]) -> fmt::Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", expr)?;
+    }
+    Ok(())
+}
+
+/// Prints an expression back out as Lox source. Parentheses are only added
+/// where the precedence of the tree needs them.
+/// # Example
+/// ```
+/// use rust_lox_impl::{error::error_reporter::ErrorReporter, parser::Parser, scanner::Scanner};
+///
+/// let print = |src: &str| {
+///     let (tokens, error_reporter) = Scanner::new(src.to_owned(), ErrorReporter::new()).scan_tokens();
+///     let (expr, _) = Parser::new(tokens, error_reporter).parse_expression();
+///     expr.unwrap().to_string()
+/// };
+///
+/// assert_eq!(print("1+2*3"), "1 + 2 * 3");
+/// assert_eq!(print("(1 + 2) * -x"), "(1 + 2) * -x");
+/// assert_eq!(print("a or b and !c ? f(x)[0] : y.z"), "a or b and !c ? f(x)[0] : y.z");
+/// assert_eq!(print("y.z = - -i++"), "y.z = - -i++");
+///
+/// // Compound assignment is desugared without a grouping, but still needs the parentheses.
+/// assert_eq!(print("x *= 1 + 2"), "x = x * (1 + 2)");
+/// ```
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precedence = self.precedence();
+        match self {
+            Expr::Binary(BinaryExpr { lhs, operator, rhs })
+                if operator.token_type == TokenType::StarStar =>
+            {
+                // The only right associative binary operator.
+                lhs.fmt_at(f, precedence.next())?;
+                write!(f, " {} ", operator.lexeme)?;
+                rhs.fmt_at(f, precedence)
+            }
+            Expr::Binary(BinaryExpr { lhs, operator, rhs })
+            | Expr::Logical(BinaryExpr { lhs, operator, rhs }) => {
+                // The rest of the binary operators are left associative.
+                lhs.fmt_at(f, precedence)?;
+                write!(f, " {} ", operator.lexeme)?;
+                rhs.fmt_at(f, precedence.next())
+            }
+            Expr::Grouping(GroupingExpr { expr }) => write!(f, "({})", expr),
+            Expr::Literal(LiteralExpr { token }) => write!(f, "{}", token.lexeme),
+            Expr::Unary(UnaryExpr { operator, rhs }) => {
+                write!(f, "{}", operator.lexeme)?;
+
+                // Keep `- -x` from running together into `--x`.
+                if let Expr::Unary(UnaryExpr {
+                    operator: inner, ..
+                })
+                | Expr::IncDec(IncDecExpr {
+                    operator: inner,
+                    is_prefix: true,
+                    ..
+                }) = rhs.as_ref()
+                {
+                    if inner.lexeme.starts_with(&operator.lexeme) {
+                        write!(f, " ")?;
+                    }
+                }
+                rhs.fmt_at(f, precedence)
+            }
+            Expr::IncDec(IncDecExpr {
+                variable,
+                operator,
+                is_prefix,
+                ..
+            }) => {
+                if *is_prefix {
+                    write!(f, "{}{}", operator.lexeme, variable.lexeme)
+                } else {
+                    write!(f, "{}{}", variable.lexeme, operator.lexeme)
+                }
+            }
+            Expr::Variable(VariableExpr { name, .. }) => write!(f, "{}", name.lexeme),
+            Expr::Assignment(AssignmentExpr { variable, expr, .. }) => {
+                write!(f, "{} = {}", variable.lexeme, expr)
+            }
+            Expr::Ternary(TernaryExpr {
+                condition,
+                then_expr,
+                else_expr,
+            }) => {
+                condition.fmt_at(f, Precedence::Or)?;
+                write!(f, " ? {} : ", then_expr)?;
+                else_expr.fmt_at(f, Precedence::Ternary)
+            }
+            Expr::Interpolation(InterpolationExpr { parts }) => {
+                write!(f, "\"")?;
+                for (i, part) in parts.iter().enumerate() {
+                    match part {
+                        // The even parts are the text between the embedded expressions.
+                        Expr::Literal(LiteralExpr {
+                            token:
+                                Token {
+                                    token_type: TokenType::String(s),
+                                    ..
+                                },
+                        }) if i % 2 == 0 => write!(f, "{}", escape_string(s))?,
+                        expr => write!(f, "${{{}}}", expr)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Expr::Call(CallExpr { callee, args, .. }) => {
+                callee.fmt_at(f, Precedence::Call)?;
+                write!(f, "(")?;
+                fmt_list(f, args)?;
+                write!(f, ")")
+            }
+            Expr::Get(GetExpr { object, name }) => {
+                object.fmt_at(f, Precedence::Call)?;
+                write!(f, ".{}", name.lexeme)
+            }
+            Expr::Set(SetExpr {
+                object,
+                name,
+                value,
+            }) => {
+                object.fmt_at(f, Precedence::Call)?;
+                write!(f, ".{} = {}", name.lexeme, value)
+            }
+            Expr::This(_) => write!(f, "this"),
+            Expr::Array(ArrayExpr { elements }) => {
+                write!(f, "[")?;
+                fmt_list(f, elements)?;
+                write!(f, "]")
+            }
+            Expr::Index(IndexExpr { object, index, .. }) => {
+                object.fmt_at(f, Precedence::Call)?;
+                write!(f, "[{}]", index)
+            }
+            Expr::IndexSet(IndexSetExpr {
+                object,
+                index,
+                value,
+                ..
+            }) => {
+                object.fmt_at(f, Precedence::Call)?;
+                write!(f, "[{}] = {}", index, value)
+            }
+            Expr::Slice(SliceExpr {
+                object, start, end, ..
+            }) => {
+                object.fmt_at(f, Precedence::Call)?;
+                write!(f, "[")?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Escapes the text of a string so it can be written back out between quotes.
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' | '"' | '$' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_rest {
+            write!(f, "...")?;
+        }
+        write!(f, "{}", self.name.lexeme)?;
+        if let Some(default) = &self.default {
+            write!(f, " = {}", default)?;
+        }
+        Ok(())
+    }
+}
+
+impl Stmt {
+    /// Whether the statement ends with an `if` that doesn't have an `else`, so an
+    /// `else` written straight after it would be taken as its own.
+    fn ends_with_open_if(&self) -> bool {
+        match self {
+            Stmt::If(IfStmt {
+                else_branch: None, ..
+            }) => true,
+            Stmt::If(IfStmt {
+                else_branch: Some(else_branch),
+                ..
+            }) => else_branch.ends_with_open_if(),
+            Stmt::While(WhileStmt { body, .. })
+            | Stmt::ForEach(ForEachStmt { body, .. })
+            | Stmt::Repeat(RepeatStmt { body, .. }) => body.ends_with_open_if(),
+            _ => false,
+        }
+    }
+
+    /// Writes the statement, indenting any lines after the first by `indent` levels.
+    /// The first line is left to the caller, since nested statements often continue
+    /// a line (like the body of `while (x) ...`).
+    fn fmt_indented(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        match self {
+            Stmt::VariableDeclaration(VariableDeclarationStmt {
+                name,
+                initializer,
+                is_const,
+            }) => {
+                let keyword = if *is_const { "const" } else { "var" };
+                match initializer {
+                    Some(initializer) => {
+                        write!(f, "{} {} = {};", keyword, name.lexeme, initializer)
+                    }
+                    None => write!(f, "{} {};", keyword, name.lexeme),
+                }
+            }
+            Stmt::Expression(ExpressionStmt { expr }) => write!(f, "{};", expr),
+            Stmt::While(WhileStmt {
+                condition,
+                body,
+                increment,
+                label,
+            }) => {
+                fmt_label(f, label)?;
+
+                // Only desugared for loops have an increment.
+                match increment {
+                    Some(increment) => write!(f, "for (; {}; {}) ", condition, increment)?,
+                    None => write!(f, "while ({}) ", condition)?,
+                }
+                body.fmt_indented(f, indent)
+            }
+            Stmt::DoWhile(DoWhileStmt {
+                body,
+                condition,
+                label,
+            }) => {
+                fmt_label(f, label)?;
+                write!(f, "do ")?;
+                body.fmt_indented(f, indent)?;
+                write!(f, " while ({});", condition)
+            }
+            Stmt::ForEach(ForEachStmt {
+                variable,
+                iterable,
+                body,
+                label,
+                ..
+            }) => {
+                fmt_label(f, label)?;
+                write!(f, "foreach ({} in {}) ", variable.lexeme, iterable)?;
+                body.fmt_indented(f, indent)
+            }
+            Stmt::Repeat(RepeatStmt {
+                count, body, label, ..
+            }) => {
+                fmt_label(f, label)?;
+                write!(f, "repeat ({}) ", count)?;
+                body.fmt_indented(f, indent)
+            }
+            Stmt::FunctionDeclaration(function) => {
+                write!(f, "fun ")?;
+                fmt_function(f, function, indent)
+            }
+            Stmt::ClassDeclaration(ClassDeclarationStmt {
+                name,
+                methods,
+                getters,
+                class_methods,
+            }) => {
+                writeln!(f, "class {} {{", name.lexeme)?;
+                for method in class_methods {
+                    write!(f, "{}class ", "    ".repeat(indent + 1))?;
+                    fmt_function(f, method, indent + 1)?;
+                    writeln!(f)?;
+                }
+                for method in methods {
+                    write!(f, "{}", "    ".repeat(indent + 1))?;
+                    fmt_function(f, method, indent + 1)?;
+                    writeln!(f)?;
+                }
+                for getter in getters {
+                    write!(f, "{}{} ", "    ".repeat(indent + 1), getter.name.lexeme)?;
+                    fmt_block(f, &getter.body, indent + 1)?;
+                    writeln!(f)?;
+                }
+                write!(f, "{}}}", "    ".repeat(indent))
+            }
+            Stmt::Block(BlockStmt { body }) => fmt_block(f, body, indent),
+            Stmt::If(IfStmt {
+                condition,
+                then_branch,
+                else_branch,
+            }) => {
+                write!(f, "if ({}) ", condition)?;
+                if else_branch.is_some() && then_branch.ends_with_open_if() {
+                    // Otherwise the else would be read back as belonging to the inner if.
+                    let body = vec![(**then_branch).clone()];
+                    Stmt::Block(BlockStmt { body }).fmt_indented(f, indent)?;
+                } else {
+                    then_branch.fmt_indented(f, indent)?;
+                }
+                if let Some(else_branch) = else_branch {
+                    write!(f, " else ")?;
+                    else_branch.fmt_indented(f, indent)?;
+                }
+                Ok(())
+            }
+            Stmt::Return(ReturnStmt { value, .. }) => match value {
+                Some(value) => write!(f, "return {};", value),
+                None => write!(f, "return;"),
+            },
+            Stmt::Break(BreakStmt { label, .. }) => match label {
+                Some(label) => write!(f, "break {};", label.lexeme),
+                None => write!(f, "break;"),
+            },
+            Stmt::Continue(ContinueStmt { label, .. }) => match label {
+                Some(label) => write!(f, "continue {};", label.lexeme),
+                None => write!(f, "continue;"),
+            },
+            Stmt::Try(TryStmt {
+                body,
+                catch_variable,
+                catch_body,
+            }) => {
+                write!(f, "try ")?;
+                fmt_block(f, &body.body, indent)?;
+                write!(f, " catch ({}) ", catch_variable.lexeme)?;
+                fmt_block(f, &catch_body.body, indent)
+            }
+            Stmt::Throw(ThrowStmt { value, .. }) => write!(f, "throw {};", value),
+            Stmt::Import(ImportStmt { path, .. }) => {
+                write!(f, "import \"{}\";", escape_string(path))
+            }
+        }
+    }
+}
+
+/// Writes a loop's label, if it has one, in front of the loop.
+fn fmt_label(f: &mut fmt::Formatter, label: &Option<Token>) -> fmt::Result {
+    match label {
+        Some(label) => write!(f, "{}: ", label.lexeme),
+        None => Ok(()),
+    }
+}
+
+/// Writes a function's name, parameters, and body, without the `fun` keyword
+/// so it can be used for methods too.
+fn fmt_function(
+    f: &mut fmt::Formatter,
+    function: &FunctionDeclarationStmt,
+    indent: usize,
+) -> fmt::Result {
+    write!(f, "{}(", function.name.lexeme)?;
+    for (i, param) in function.params.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", param)?;
+    }
+    write!(f, ") ")?;
+    fmt_block(f, &function.body, indent)
+}
+
+/// Writes a list of statements between braces, one per line.
+fn fmt_block(f: &mut fmt::Formatter, body: &[Stmt], indent: usize) -> fmt::Result {
+    if body.is_empty() {
+        return write!(f, "{{}}");
+    }
+    writeln!(f, "{{")?;
+    for stmt in body {
+        write!(f, "{}", "    ".repeat(indent + 1))?;
+        stmt.fmt_indented(f, indent + 1)?;
+        writeln!(f)?;
+    }
+    write!(f, "{}}}", "    ".repeat(indent))
+}
+
+/// Prints a statement back out as Lox source, with nested blocks indented by four spaces.
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
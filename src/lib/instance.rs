@@ -0,0 +1,71 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    class::LoxClass, error::runtime_error::RuntimeError, function::LoxFunction,
+    interpreter::RuntimeResult, object::LoxObject, token::Token,
+};
+
+/// Represents an instance of a Lox Class.
+#[derive(Clone)]
+pub struct LoxInstance {
+    /// The class this is an instance of
+    class: LoxClass,
+
+    /// The state of the instance. Fields don't have to be declared ahead of time,
+    /// assigning to a field which doesn't exist yet creates it.
+    fields: HashMap<String, LoxObject>,
+}
+
+impl LoxInstance {
+    /// Creates a new instance of the class with no fields set.
+    pub fn new(class: LoxClass) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Looks up a property on the instance. Fields shadow methods, and methods
+    /// come back bound to the instance so that `this` works inside them (which is
+    /// why this takes the shared instance rather than `&self`).
+    pub fn get(instance: &Rc<RefCell<Self>>, name: &Token) -> RuntimeResult<LoxObject> {
+        let this = instance.borrow();
+
+        if let Some(field) = this.fields.get(&name.lexeme) {
+            return Ok(field.clone());
+        }
+
+        if let Some(method) = this.class.find_method(&name.lexeme) {
+            let method = method.bind(LoxObject::Instance(instance.clone()));
+            return Ok(LoxObject::Function(Box::new(method)));
+        }
+
+        Err(RuntimeError::new(
+            name.clone(),
+            format!("Undefined property '{}'.", name.lexeme),
+        ))
+    }
+
+    /// Looks up a getter for the property, bound to the instance and ready to be called.
+    /// Like methods, getters are shadowed by fields.
+    pub fn getter(instance: &Rc<RefCell<Self>>, name: &Token) -> Option<LoxFunction> {
+        let this = instance.borrow();
+        if this.fields.contains_key(&name.lexeme) {
+            return None;
+        }
+        this.class
+            .find_getter(&name.lexeme)
+            .map(|getter| getter.bind(LoxObject::Instance(instance.clone())))
+    }
+
+    /// Sets a field on the instance, creating it if it doesn't exist yet.
+    pub fn set(&mut self, name: &Token, value: LoxObject) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+impl std::fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name())
+    }
+}
@@ -1,22 +1,237 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use crate::{
+    builtin_functions::Rng,
+    callable::LoxCallable,
+    class::LoxClass,
     environment::Environment,
-    error::{error_reporter::ErrorReporter, runtime_error::RuntimeError},
+    error::{
+        error_reporter::ErrorReporter,
+        runtime_error::{RuntimeError, RuntimeErrorCtx},
+    },
     function::LoxFunction,
     grammar::{
-        AssignmentExpr, BinaryExpr, BlockStmt, CallExpr, Expr, FunctionDeclarationStmt,
-        GroupingExpr, IfStmt, LiteralExpr, ReturnStmt, Stmt, UnaryExpr, VariableDeclarationStmt,
+        ArrayExpr, AssignmentExpr, BinaryExpr, BlockStmt, BreakStmt, CallExpr,
+        ClassDeclarationStmt, ContinueStmt, DoWhileStmt, Expr, ExpressionStmt, ForEachStmt,
+        FunctionDeclarationStmt, GetExpr, GroupingExpr, IfStmt, ImportStmt, IncDecExpr, IndexExpr,
+        IndexSetExpr, InterpolationExpr, LiteralExpr, RepeatStmt, ReturnStmt, SetExpr, SliceExpr,
+        Stmt, TernaryExpr, ThisExpr, ThrowStmt, TryStmt, UnaryExpr, VariableDeclarationStmt,
         VariableExpr, WhileStmt,
     },
+    instance::LoxInstance,
     object::LoxObject,
-    token::TokenType,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    token::{Token, TokenType},
 };
 
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
 
+/// Signals how execution should proceed after a statement has been executed.
+#[derive(Clone, PartialEq)]
+pub enum ControlFlow {
+    /// Carry on with the next statement.
+    Normal,
+
+    /// A return statement was hit (i.e. a return statement halfway through
+    /// a function body), carrying the value to return.
+    Return(LoxObject),
+
+    /// A break statement was hit, so the enclosing loop should stop. Carries the label
+    /// of the loop to break out of, if one was given, otherwise it's the innermost loop.
+    Break(Option<String>),
+
+    /// A continue statement was hit, so the enclosing loop should skip to the next iteration.
+    /// Carries the label of the loop to continue, like `Break`.
+    Continue(Option<String>),
+
+    /// A return statement returning a call to a Lox function, like `return f(x);`. The
+    /// call is left to the function being returned from, so that a function calling
+    /// itself can loop rather than recursing.
+    TailCall(Box<TailCall>),
+}
+
+/// A call waiting to be made by a `ControlFlow::TailCall`. The arguments have
+/// already been evaluated and checked against the function's arity.
+#[derive(Clone, PartialEq)]
+pub struct TailCall {
+    pub function: LoxFunction,
+    pub args: Vec<LoxObject>,
+    pub closing_paren: Token,
+}
+
 /// The interpreter is responsible for "running" the program.
-#[derive(Clone)]
 pub struct Interpreter {
     pub error_reporter: ErrorReporter,
+
+    /// Where the `input` builtin reads lines from. Defaults to stdin, but can be
+    /// swapped out to feed a script input from somewhere else.
+    pub input: Box<dyn BufRead>,
+
+    /// Where `print` and the other builtins write to. Defaults to stdout, but can be
+    /// swapped out to capture a script's output.
+    pub output: Box<dyn Write>,
+
+    /// Where `clock` and `clock_millis` get the time from, in seconds since the unix
+    /// epoch. Defaults to the system clock, but can be swapped out so scripts that
+    /// use the time behave the same on every run.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.clock = Box::new(|| 42.5);
+    /// let (output, _) = interpreter.run_to_string("print(clock()); print(clock_millis());");
+    /// assert_eq!(output, "42.5\n42500\n");
+    /// ```
+    pub clock: Box<dyn Fn() -> f64>,
+
+    /// Write errors to `output` instead of stderr, so they come out in the same order as
+    /// everything the program prints (handy for testing a script's output).
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.errors_to_output = true;
+    /// let (output, had_error) = interpreter.run_to_string("print(\"before\");\nprint(missing);\nprint(\"after\");");
+    /// assert!(had_error);
+    /// assert!(output.starts_with("before\n[Line 2, Col 7] Error at 'missing': Undefined variable missing\n"));
+    /// assert!(output.ends_with("after\n"));
+    ///
+    /// let (output, _) = interpreter.run_to_string("print(1 +);");
+    /// assert_eq!(output, "[Line 1, Col 10] Error at ')': Expected Expression\n");
+    /// ```
+    pub errors_to_output: bool,
+
+    /// The source code being run, used to show the offending line when
+    /// reporting a runtime error.
+    pub source: Rc<str>,
+
+    /// What to call the source code in error messages, usually the name of the file
+    /// it came from. Errors are labelled like `[file.lox:12, Col 5]` when it's set.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.source_name = Some("main.lox".to_owned());
+    /// let errors = interpreter.run_source("print(missing);").unwrap_err();
+    /// assert!(errors[0].starts_with("[main.lox:1, Col 7] Error at 'missing'"));
+    /// let errors = interpreter.run_source("var = 1;").unwrap_err();
+    /// assert!(errors[0].starts_with("[main.lox:1, Col 5] Error at '='"));
+    /// ```
+    pub source_name: Option<String>,
+
+    /// The file the source code came from, if any. Imports are found relative to it
+    /// (or to the working directory when there isn't one).
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let dir = std::env::temp_dir().join("rlox_import_example");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("greet.lox"), "fun greet(name) { return \"hi \" + name; }").unwrap();
+    /// std::fs::write(dir.join("loop.lox"), "import \"loop.lox\";").unwrap();
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.script_path = Some(dir.join("main.lox"));
+    /// let (output, had_error) = interpreter.run_to_string("import \"greet.lox\";\nprint(greet(\"bob\"));");
+    /// assert_eq!(output, "hi bob\n");
+    /// assert!(!had_error);
+    ///
+    /// // A file that ends up importing itself is an error rather than an infinite loop.
+    /// let errors = interpreter.run_source("import \"loop.lox\";").unwrap_err();
+    /// assert!(errors[0].contains("Circular import of loop.lox"));
+    ///
+    /// // An error inside an imported function points into the file it was declared in,
+    /// // even when it's called from the importer.
+    /// std::fs::write(dir.join("broken.lox"), "fun broken() {\n  return missing;\n}").unwrap();
+    /// interpreter.source_name = Some("main.lox".to_owned());
+    /// let errors = interpreter.run_source("import \"broken.lox\";\nbroken();").unwrap_err();
+    /// let expected = [
+    ///     "[broken.lox:2, Col 10] Error at 'missing': Undefined variable missing",
+    ///     "  |",
+    ///     "2 |   return missing;",
+    ///     "  |          ^^^^^^^",
+    /// ];
+    /// assert_eq!(errors[0], expected.join("\n"));
+    /// ```
+    pub script_path: Option<PathBuf>,
+
+    /// The files partway through importing another file, for catching import cycles.
+    importing: Vec<PathBuf>,
+
+    /// The global environment programs are run in.
+    environment: Environment,
+
+    /// Print the tokens the scanner produces to the output before running anything.
+    pub dump_tokens: bool,
+
+    /// Print the syntax tree the parser produces to the output before running anything.
+    pub dump_ast: bool,
+
+    /// Let `+` concatenate a string and a number, converting the number the same way
+    /// `print` would. Off by default, where that's an error.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// let (_, had_error) = interpreter.run_to_string("print(\"count: \" + 5);");
+    /// assert!(had_error);
+    ///
+    /// interpreter.coerce_strings = true;
+    /// let (output, _) = interpreter.run_to_string("print(\"count: \" + 5, 1.5 + \"x\");");
+    /// assert_eq!(output, "count: 5 1.5x\n");
+    /// ```
+    pub coerce_strings: bool,
+
+    /// Treat `0` as falsy along with `nil` and `false`, which is how this interpreter
+    /// used to behave. Off by default to match standard Lox.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let source = "if (0) print(\"truthy\"); else print(\"falsy\");";
+    /// let mut interpreter = Interpreter::new();
+    /// assert_eq!(interpreter.run_to_string(source).0, "truthy\n");
+    ///
+    /// interpreter.zero_is_falsy = true;
+    /// assert_eq!(interpreter.run_to_string(source).0, "falsy\n");
+    /// ```
+    pub zero_is_falsy: bool,
+
+    /// The random number generator behind the `random` builtins. Seeded from the clock,
+    /// unless a script reseeds it with `seed_random`.
+    pub rng: Rng,
+
+    /// How deeply calls can nest before a "Stack overflow" error is raised. Each Lox call
+    /// recurses through the interpreter, so without a limit runaway recursion would
+    /// overflow the real stack and crash. The thread running the interpreter needs
+    /// enough stack to reach the limit (`rlox_one` gives it a big one).
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.max_call_depth = 20;
+    /// let errors = interpreter.run_source("fun f() { f(); }\nf();").unwrap_err();
+    /// assert!(errors[0].starts_with("[Line 1, Col 13] Error at ')': Stack overflow."));
+    /// ```
+    pub max_call_depth: usize,
+
+    /// How many calls deep the program currently is.
+    call_depth: usize,
 }
 
 impl Interpreter {
@@ -24,126 +239,590 @@ impl Interpreter {
     pub fn new() -> Self {
         Self {
             error_reporter: ErrorReporter::new(),
+            input: Box::new(BufReader::new(io::stdin())),
+            output: Box::new(io::stdout()),
+            clock: Box::new(system_clock),
+            source: "".into(),
+            errors_to_output: false,
+            source_name: None,
+            script_path: None,
+            importing: vec![],
+            environment: Environment::new(),
+            dump_tokens: false,
+            dump_ast: false,
+            coerce_strings: false,
+            zero_is_falsy: false,
+            rng: Rng::from_clock(),
+            max_call_depth: 1000,
+            call_depth: 0,
+        }
+    }
+
+    /// Runs a Lox program, capturing everything it prints rather than writing it to the
+    /// usual output. Returns the captured output along with whether an error occurred.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// let (output, had_error) = interpreter.run_to_string("print(\"hi\");");
+    /// assert_eq!(output, "hi\n");
+    /// assert!(!had_error);
+    /// ```
+    pub fn run_to_string(&mut self, src: &str) -> (String, bool) {
+        let buffer = SharedBuffer::default();
+        let output = std::mem::replace(&mut self.output, Box::new(buffer.clone()));
+        let had_error = self.run_source(src).is_err();
+        self.output = output;
+
+        (buffer.contents(), had_error)
+    }
+
+    /// Takes some Lox source code through each step of the lifecycle (scanning, parsing,
+    /// resolving, and interpreting). Nothing is run if there are static errors. Returns every
+    /// error message reported along the way.
+    ///
+    /// Runtime errors are reported to `self.error_reporter`, which is reset for each run,
+    /// so checking it afterwards tells a static error apart from a runtime one.
+    pub fn run_source(&mut self, src: &str) -> Result<(), Vec<String>> {
+        self.error_reporter = self.new_runtime_error_reporter();
+
+        let scanner = Scanner::new(src.to_owned(), self.new_error_reporter());
+        let (tokens, error_reporter) = scanner.scan_tokens();
+        self.debug_dump(self.dump_tokens, &tokens);
+        self.run_tokens(src, tokens, error_reporter, false)
+    }
+
+    /// Runs a line typed into the REPL. Works just like `run_source`, except that a line
+    /// which is only an expression doesn't need a semicolon, and its value gets printed.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::interpreter::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new();
+    ///
+    /// // A line starting with a statement keyword is a statement, which needs its semicolon.
+    /// let lines = [
+    ///     "var y = 1",
+    ///     "const x = 1",
+    ///     "throw \"oops\"",
+    ///     "try { print(1); } catch (e) { print(e); } print(2)",
+    ///     "import \"other.lox\"",
+    /// ];
+    /// for line in lines {
+    ///     let errors = interpreter.run_repl_line(line).unwrap_err();
+    ///     assert!(errors[0].ends_with("Expected ';'"), "{}", errors[0]);
+    /// }
+    /// ```
+    pub fn run_repl_line(&mut self, src: &str) -> Result<(), Vec<String>> {
+        self.error_reporter = self.new_runtime_error_reporter();
+
+        let scanner = Scanner::new(src.to_owned(), self.new_error_reporter());
+        let (tokens, error_reporter) = scanner.scan_tokens();
+        self.debug_dump(self.dump_tokens, &tokens);
+        if !is_bare_expression(&tokens) {
+            return self.run_tokens(src, tokens, error_reporter, true);
+        }
+
+        let parser = Parser::new(tokens, error_reporter);
+        let (expr, error_reporter) = parser.parse_expression();
+        self.ast_dump(&expr);
+        let expr = match expr {
+            Some(expr) if !error_reporter.had_error => expr,
+            _ => return Err(self.static_errors(error_reporter)),
+        };
+
+        // A top level expression can't declare any locals, so there's nothing to resolve.
+        // Every variable in it is global.
+        self.source = src.into();
+        let mut environment = self.environment.clone();
+        let result = self
+            .evaluate(&expr, &mut environment)
+            .and_then(|value| match value {
+                // Like Python's REPL, don't bother echoing nothing.
+                LoxObject::Nil => Ok(()),
+                value => writeln!(self.output, "{}", value)
+                    .map_err(|e| RuntimeError::native(format!("Couldn't write output: {}", e))),
+            });
+        if let Err(e) = result {
+            self.report_error(e);
+            return Err(self.error_reporter.messages.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Creates an error reporter which labels errors with the source name, if there is one.
+    fn new_error_reporter(&self) -> ErrorReporter {
+        ErrorReporter {
+            file_name: self.source_name.clone(),
+            print_errors: !self.errors_to_output,
+            ..ErrorReporter::new()
+        }
+    }
+
+    /// Creates the error reporter for runtime errors. These label themselves with the
+    /// source they came from (see `report_error`), which might not be this one.
+    fn new_runtime_error_reporter(&self) -> ErrorReporter {
+        ErrorReporter {
+            file_name: None,
+            ..self.new_error_reporter()
+        }
+    }
+
+    /// The source code currently being run, for functions declared in it to hold on to.
+    pub(crate) fn current_source(&self) -> Source {
+        Source {
+            name: self.source_name.clone(),
+            text: self.source.clone(),
+        }
+    }
+
+    /// Reports a runtime error, along with the line of source it happened on. Errors
+    /// inside a function already carry the source it was declared in.
+    fn report_error(&mut self, error: RuntimeError) {
+        let error = error.in_source(&self.current_source());
+        self.error_reporter.error(error);
+        if let Some(msg) = self.error_reporter.messages.last().cloned() {
+            self.write_errors(&[msg]);
+        }
+    }
+
+    /// Takes the messages from the error reporter that caught some static errors,
+    /// writing them to the output if that's where errors go.
+    fn static_errors(&mut self, error_reporter: ErrorReporter) -> Vec<String> {
+        self.write_errors(&error_reporter.messages);
+        error_reporter.messages
+    }
+
+    /// Writes errors to the output if `errors_to_output` is set. Otherwise they've
+    /// already been printed to stderr by the error reporter.
+    fn write_errors(&mut self, messages: &[String]) {
+        if self.errors_to_output {
+            // There's nowhere left to report a failure to write an error
+            for msg in messages {
+                let _ = writeln!(self.output, "{}", msg);
+            }
+            let _ = self.output.flush();
+        }
+    }
+
+    /// Pretty prints the debug representation of something to the output, if the
+    /// corresponding dump flag is set.
+    fn debug_dump(&mut self, enabled: bool, value: &impl std::fmt::Debug) {
+        if enabled {
+            // This is only for debugging, so failing to write isn't worth reporting
+            let _ = writeln!(self.output, "{:#?}", value);
+        }
+    }
+
+    /// Prints the syntax tree back out as Lox source to the output, if the
+    /// `dump_ast` flag is set.
+    fn ast_dump<'a, T: std::fmt::Display + 'a>(&mut self, nodes: impl IntoIterator<Item = &'a T>) {
+        if self.dump_ast {
+            for node in nodes {
+                let _ = writeln!(self.output, "{}", node);
+            }
+        }
+    }
+
+    /// Parses, resolves, and interprets some scanned source code. Unused local
+    /// variables are only warned about outside the REPL.
+    fn run_tokens(
+        &mut self,
+        src: &str,
+        tokens: Vec<Token>,
+        error_reporter: ErrorReporter,
+        is_repl: bool,
+    ) -> Result<(), Vec<String>> {
+        let parser = Parser::new(tokens, error_reporter);
+        let (stmts, error_reporter) = parser.parse();
+        self.ast_dump(&stmts);
+        let mut resolver = Resolver::new(stmts, error_reporter);
+        resolver.warn_unused_locals = !is_repl;
+        let (stmts, error_reporter) = resolver.resolve();
+        if error_reporter.had_error {
+            return Err(self.static_errors(error_reporter));
+        }
+        self.write_errors(&error_reporter.warnings);
+
+        self.source = src.into();
+        self.interpret(stmts);
+        if self.error_reporter.had_error {
+            Err(self.error_reporter.messages.clone())
+        } else {
+            Ok(())
         }
     }
 
-    /// Executes a list of Lox Statements in a dedicated environment.
-    pub fn interpret(&mut self, stmts: Vec<Stmt>) {
-        let mut environment = Environment::new();
-        for stmt in stmts.into_iter() {
-            self.execute(stmt, &mut environment);
+    /// Executes a list of Lox Statements in the interpreter's environment, which
+    /// sticks around between calls (so the REPL remembers earlier lines).
+    /// A runtime error aborts the top level statement it occurred in, then
+    /// gets reported before moving on to the next statement.
+    ///
+    /// Returns the value of the last statement if it's an expression statement,
+    /// or `nil` otherwise (including when it fails).
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{interpreter::Interpreter, object::LoxObject, parser::parse, scanner::scan};
+    ///
+    /// let (tokens, _) = scan("1 + 2;");
+    /// let (stmts, _) = parse(tokens);
+    /// assert!(Interpreter::new().interpret(stmts) == LoxObject::Number(3.0));
+    ///
+    /// let (tokens, _) = scan("1 + 2;\nvar b = 3;");
+    /// let (stmts, _) = parse(tokens);
+    /// assert!(Interpreter::new().interpret(stmts) == LoxObject::Nil);
+    /// ```
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> LoxObject {
+        // Cloning an environment shares its scopes, so anything defined in the
+        // clone is still there next time.
+        let mut environment = self.environment.clone();
+        let mut last_value = LoxObject::Nil;
+        for stmt in stmts.iter() {
+            // Expression statements are evaluated directly so their value isn't lost.
+            let result = match stmt {
+                Stmt::Expression(ExpressionStmt { expr }) => self.evaluate(expr, &mut environment),
+                stmt => self.execute(stmt, &mut environment).map(|_| LoxObject::Nil),
+            };
+
+            last_value = match result {
+                Ok(value) => value,
+                Err(e) => {
+                    self.report_error(e);
+                    LoxObject::Nil
+                }
+            };
         }
+        last_value
     }
 
-    /// Execute a single Lox statement in the given environemt. Returns an optional
-    /// return value for handling early returns (i.e. a return statement halfway through
-    /// a function body)
-    pub fn execute(&mut self, stmt: Stmt, exec_env: &mut Environment) -> Option<LoxObject> {
+    /// Execute a single Lox statement in the given environemt. Returns a `ControlFlow`
+    /// signal for handling early returns and loop control, or the runtime error that
+    /// stopped the statement from executing.
+    ///
+    /// Statements are executed by reference, so loop bodies and function bodies
+    /// don't get copied each time they run.
+    pub fn execute(
+        &mut self,
+        stmt: &Stmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<ControlFlow> {
         match stmt {
             // An expression statement doesn't return anything, so just
-            // evaluate the expr and report an error if there is one.
-            // Then return None.
+            // evaluate the expr, then carry on.
             Stmt::Expression(stmt) => {
-                let _ = self
-                    .evaluate(stmt.expr, exec_env)
-                    .map_err(|e| self.error_reporter.error(e));
-                None
+                self.evaluate(&stmt.expr, exec_env)?;
+                Ok(ControlFlow::Normal)
             }
             // An variable declaration statement doesn't return anything, so just
-            // execute the stmt and report an error if there is one.
-            // Then return None.
+            // execute the stmt, then carry on.
             Stmt::VariableDeclaration(var_dec_stmt) => {
-                let _ = self
-                    .variable_statement(var_dec_stmt, exec_env)
-                    .map_err(|e| self.error_reporter.error(e));
-                None
-            }
-            // Executing a successfully parsed block stmt won't fail,
-            // (if the body fails to execute because of some error, it will
-            // be handled by another branch of this match stmt)
-            // so just bubble up the optional return value.
+                self.variable_statement(var_dec_stmt, exec_env)?;
+                Ok(ControlFlow::Normal)
+            }
+            // Block, if, and while stmts can all alter control flow,
+            // so bubble up the control flow signal.
             Stmt::Block(block_stmt) => self.execute_block(block_stmt, exec_env),
-            // An if stmt can fail (because it has to evaluate the condition)
-            // and can have a return value, so if there's an
-            // error report it and return `None`, or if no error bubble up the
-            // optional return value.
-            Stmt::If(if_stmt) => self
-                .if_statement(if_stmt, exec_env)
-                .map_err(|e| self.error_reporter.error(e))
-                .ok()
-                .flatten(),
+            Stmt::If(if_stmt) => self.if_statement(if_stmt, exec_env),
+            Stmt::While(while_stmt) => self.while_statement(while_stmt, exec_env),
+            Stmt::DoWhile(do_while_stmt) => self.do_while_statement(do_while_stmt, exec_env),
+            Stmt::ForEach(foreach_stmt) => self.foreach_statement(foreach_stmt, exec_env),
+            Stmt::Repeat(repeat_stmt) => self.repeat_statement(repeat_stmt, exec_env),
             // Interpreting a function declaration statement doesn't return anything
             // and can't fail, so just
-            // execute the stmt and return None.
+            // execute the stmt and carry on.
             Stmt::FunctionDeclaration(func_decl_stmt) => {
-                self.function_declaration(func_decl_stmt, exec_env);
-                None
-            }
-            // Return statement always returns something, hence the name. Report any error then throw it away
-            // and return the return value.
-            Stmt::Return(return_stmt) => self
-                .return_statement(return_stmt, exec_env)
-                .map_err(|e| self.error_reporter.error(e))
-                .ok(),
-            // A while stmt can fail (because it has to evaluate the condition)
-            // and can have a return value, so if there's an
-            // error report it and return `None`, or if no error bubble up the
-            // optional return value.
-            Stmt::While(while_stmt) => self
-                .while_statement(while_stmt, exec_env)
-                .map_err(|e| self.error_reporter.error(e))
-                .ok()
-                .flatten(),
+                self.function_declaration(func_decl_stmt, exec_env)?;
+                Ok(ControlFlow::Normal)
+            }
+            // Same deal as a function declaration.
+            Stmt::ClassDeclaration(class_decl_stmt) => {
+                self.class_declaration(class_decl_stmt, exec_env)?;
+                Ok(ControlFlow::Normal)
+            }
+            // Return statement always returns something, hence the name.
+            Stmt::Return(return_stmt) => self.return_statement(return_stmt, exec_env),
+            // The parser guarantees these only appear inside a loop, which
+            // is where they get handled.
+            Stmt::Break(BreakStmt { label, .. }) => {
+                Ok(ControlFlow::Break(label.as_ref().map(|l| l.lexeme.clone())))
+            }
+            Stmt::Continue(ContinueStmt { label, .. }) => Ok(ControlFlow::Continue(
+                label.as_ref().map(|l| l.lexeme.clone()),
+            )),
+            Stmt::Try(try_stmt) => self.try_statement(try_stmt, exec_env),
+            Stmt::Import(import_stmt) => {
+                self.import_statement(import_stmt)?;
+                Ok(ControlFlow::Normal)
+            }
+            // A thrown value travels up like any other runtime error, until it's
+            // caught or reaches the top level.
+            Stmt::Throw(ThrowStmt { keyword, value }) => {
+                let value = self.evaluate(value, exec_env)?;
+                Err(RuntimeError::Thrown(
+                    RuntimeErrorCtx {
+                        token: keyword.clone(),
+                    },
+                    Box::new(value),
+                ))
+            }
         }
     }
 
     /// Execute a while statement
     fn while_statement(
         &mut self,
-        WhileStmt { condition, body }: WhileStmt,
+        WhileStmt {
+            condition,
+            body,
+            increment,
+            label,
+        }: &WhileStmt,
         exec_env: &mut Environment,
-    ) -> RuntimeResult<Option<LoxObject>> {
+    ) -> RuntimeResult<ControlFlow> {
         // If the condition evaluates without an error and the result
         // is "truthy", execute the body.
-        while self.evaluate(condition.clone(), exec_env)?.is_truthy() {
-            // Execute the body of the while statement. If if has a return value (i.e. we hit a return statement),
-            // return the return value.
-            let maybe_return = self.execute(*body.clone(), exec_env);
-            if maybe_return.is_some() {
-                return Ok(maybe_return);
+        while self.evaluate_condition(condition, exec_env)? {
+            // Execute the body of the while statement. If we hit a break, stop looping.
+            // If we hit a return statement, or a break or continue meant for a loop
+            // further out, bubble it up.
+            match self.execute(body, exec_env)? {
+                ControlFlow::Normal => {}
+                ControlFlow::Break(target) if is_aimed_at(&target, label) => break,
+                ControlFlow::Continue(target) if is_aimed_at(&target, label) => {}
+                flow => return Ok(flow),
+            }
+
+            // The increment of a desugared for loop runs even if the body hit a continue.
+            if let Some(inc) = increment {
+                self.evaluate(inc, exec_env)?;
             }
         }
 
         // No return statement was hit in the while loop,
-        // so return None.
-        Ok(None)
+        // so carry on.
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Execute a do while statement. Same as a while statement, except the condition
+    /// is checked after the body runs rather than before.
+    fn do_while_statement(
+        &mut self,
+        DoWhileStmt {
+            body,
+            condition,
+            label,
+        }: &DoWhileStmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<ControlFlow> {
+        loop {
+            // A continue still has to check the condition before the next iteration.
+            match self.execute(body, exec_env)? {
+                ControlFlow::Normal => {}
+                ControlFlow::Break(target) if is_aimed_at(&target, label) => break,
+                ControlFlow::Continue(target) if is_aimed_at(&target, label) => {}
+                flow => return Ok(flow),
+            }
+
+            if !self.evaluate_condition(condition, exec_env)? {
+                break;
+            }
+        }
+
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Execute a foreach statement, running the body once for each element of an array
+    /// with the loop variable bound in a fresh scope.
+    fn foreach_statement(
+        &mut self,
+        ForEachStmt {
+            variable,
+            in_keyword,
+            iterable,
+            body,
+            label,
+        }: &ForEachStmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<ControlFlow> {
+        let elements = match self.evaluate(iterable, exec_env)? {
+            LoxObject::Array(elements) => elements,
+            other => {
+                return Err(RuntimeError::new(
+                    in_keyword.clone(),
+                    format!("Can only loop over an array, got {}", other.type_name()),
+                ))
+            }
+        };
+
+        // The body is allowed to change the array, so we look up each element as we
+        // go rather than holding a borrow of the array for the whole loop.
+        for i in 0.. {
+            let element = match elements.borrow().get(i) {
+                Some(element) => element.clone(),
+                None => break,
+            };
+
+            let control_flow = exec_env.in_new_local_scope(|e| {
+                e.define(&variable.lexeme, element);
+                self.execute(body, e)
+            })?;
+
+            match control_flow {
+                ControlFlow::Normal => {}
+                ControlFlow::Break(target) if is_aimed_at(&target, label) => break,
+                ControlFlow::Continue(target) if is_aimed_at(&target, label) => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Execute a repeat statement, running the body as many times as the count says.
+    /// The count has to be a whole, non-negative number.
+    fn repeat_statement(
+        &mut self,
+        RepeatStmt {
+            keyword,
+            count,
+            body,
+            label,
+        }: &RepeatStmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<ControlFlow> {
+        let times = match self.evaluate(count, exec_env)? {
+            LoxObject::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as u64,
+            LoxObject::Number(n) => {
+                return Err(RuntimeError::new(
+                    keyword.clone(),
+                    format!(
+                        "Can only repeat a whole, non-negative number of times, got {}",
+                        n
+                    ),
+                ))
+            }
+            other => {
+                return Err(RuntimeError::new(
+                    keyword.clone(),
+                    format!(
+                        "Can only repeat a number of times, got {}",
+                        other.type_name()
+                    ),
+                ))
+            }
+        };
+
+        for _ in 0..times {
+            match self.execute(body, exec_env)? {
+                ControlFlow::Normal => {}
+                ControlFlow::Break(target) if is_aimed_at(&target, label) => break,
+                ControlFlow::Continue(target) if is_aimed_at(&target, label) => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(ControlFlow::Normal)
     }
 
-    /// Executes a return statement.
+    /// Executes a return statement. Returning a call to a Lox function is a tail call,
+    /// which is handed back to the function being returned from to make.
     fn return_statement(
         &mut self,
-        ReturnStmt { value, .. }: ReturnStmt,
+        ReturnStmt { value, .. }: &ReturnStmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<ControlFlow> {
+        match value {
+            Some(Expr::Call(call)) => {
+                let (function, args) = self.prepare_call(call, exec_env)?;
+                match function.as_function() {
+                    Some(function) => Ok(ControlFlow::TailCall(Box::new(TailCall {
+                        function: function.clone(),
+                        args,
+                        closing_paren: call.closing_paren.clone(),
+                    }))),
+                    None => self
+                        .call_function(&*function, args, &call.closing_paren, exec_env)
+                        .map(ControlFlow::Return),
+                }
+            }
+            Some(expr) => self.evaluate(expr, exec_env).map(ControlFlow::Return),
+            None => Ok(ControlFlow::Return(LoxObject::Nil)),
+        }
+    }
+
+    /// Makes a tail call that can't be turned into a loop, just like a regular call.
+    pub fn finish_tail_call(
+        &mut self,
+        TailCall {
+            function,
+            args,
+            closing_paren,
+        }: TailCall,
         exec_env: &mut Environment,
     ) -> RuntimeResult<LoxObject> {
-        // Evaluate the expression if one was provided, otherwise return nil.
-        value
-            .map(|expr| self.evaluate(expr, exec_env))
-            .transpose()
-            .map(|maybe_val| maybe_val.unwrap_or(LoxObject::Nil))
+        self.call_function(&function, args, &closing_paren, exec_env)
     }
 
     /// Execute a function declaration statement. A function declaration statement cant cause
-    /// a return and can't fail (because by this point it has been parsed), so it doesn't
-    /// return anything.
+    /// a return, and only fails when it would replace a global constant. The function
+    /// captures the current environment as its closure.
     fn function_declaration(
         &mut self,
-        func_decl_stmt: FunctionDeclarationStmt,
+        func_decl_stmt: &FunctionDeclarationStmt,
         exec_env: &mut Environment,
-    ) {
+    ) -> RuntimeResult<()> {
+        exec_env.check_redeclaration(&func_decl_stmt.name)?;
+
         // Create a LoxObject for the function and define it in the current scope.
-        let name = func_decl_stmt.name.clone();
-        let function = LoxFunction::from(func_decl_stmt);
-        exec_env.define(&name.lexeme, LoxObject::Function(Box::new(function)));
+        // The closure shares that scope rather than copying it, so the function can
+        // see its own name (and any later siblings) when it's called, which is what
+        // makes recursion and mutual recursion work.
+        let function = LoxFunction::new(func_decl_stmt, exec_env.clone(), self.current_source());
+        exec_env.define(
+            &func_decl_stmt.name.lexeme,
+            LoxObject::Function(Box::new(function)),
+        );
+        Ok(())
+    }
+
+    /// Execute a class declaration statement. Like a function declaration, it can't cause
+    /// a return, and only fails when it would replace a global constant. Each method
+    /// captures the current environment as its closure.
+    fn class_declaration(
+        &mut self,
+        ClassDeclarationStmt {
+            name,
+            methods,
+            getters,
+            class_methods,
+        }: &ClassDeclarationStmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<()> {
+        exec_env.check_redeclaration(name)?;
+        let source = self.current_source();
+        let close_over = |declarations: &[FunctionDeclarationStmt]| {
+            declarations
+                .iter()
+                .map(|method| {
+                    (
+                        method.name.lexeme.clone(),
+                        LoxFunction::new(method, exec_env.clone(), source.clone()),
+                    )
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        // Create a LoxObject for the class and define it in the current scope.
+        let class = LoxClass::new(
+            name.clone(),
+            close_over(methods),
+            close_over(getters),
+            close_over(class_methods),
+        );
+        exec_env.define(&name.lexeme, LoxObject::Class(class));
+        Ok(())
     }
 
     /// Executes an if statement.
@@ -153,75 +832,407 @@ impl Interpreter {
             condition,
             then_branch,
             else_branch,
-        }: IfStmt,
+        }: &IfStmt,
         exec_env: &mut Environment,
-    ) -> RuntimeResult<Option<LoxObject>> {
-        Ok(if self.evaluate(condition, exec_env)?.is_truthy() {
+    ) -> RuntimeResult<ControlFlow> {
+        if self.evaluate_condition(condition, exec_env)? {
             // If the condition evaluates to true, execute the if branch.
-            self.execute(*then_branch, exec_env)
+            self.execute(then_branch, exec_env)
         } else if let Some(stmt) = else_branch {
             // If the condition evaluates to false and there's an else branch, execute it.
-            self.execute(*stmt, exec_env)
+            self.execute(stmt, exec_env)
         } else {
-            // We never executed anything so return None.
-            None
-        })
+            // We never executed anything so carry on.
+            Ok(ControlFlow::Normal)
+        }
+    }
+
+    /// Executes a try statement. A runtime error in the body is caught, and the catch
+    /// body runs with the thrown value (or the error's message) bound to the catch variable.
+    fn try_statement(
+        &mut self,
+        TryStmt {
+            body,
+            catch_variable,
+            catch_body,
+        }: &TryStmt,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<ControlFlow> {
+        let result = match self.execute_block(body, exec_env) {
+            // A tail call has to be made inside the try, so its errors can be caught.
+            Ok(ControlFlow::TailCall(tail_call)) => self
+                .finish_tail_call(*tail_call, exec_env)
+                .map(ControlFlow::Return),
+            result => result,
+        };
+        match result {
+            Err(error) => exec_env.in_new_local_scope(|e| {
+                e.define(&catch_variable.lexeme, error.caught_value());
+                self.execute_block(catch_body, e)
+            }),
+            result => result,
+        }
+    }
+
+    /// Executes an import statement, running the imported file in the global environment
+    /// so everything it declares is available afterwards. Errors in the imported file are
+    /// reported at the import, along with the file's name.
+    fn import_statement(&mut self, ImportStmt { keyword, path }: &ImportStmt) -> RuntimeResult<()> {
+        let import_error = |msg: String| RuntimeError::new(keyword.clone(), msg);
+
+        let base = self
+            .script_path
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""));
+        let file = fs::canonicalize(base.join(path))
+            .map_err(|e| import_error(format!("Couldn't find {}: {}", path, e)))?;
+
+        // The file doing the importing counts as being imported too, so a file that
+        // imports itself (directly or not) is caught.
+        let current = self
+            .script_path
+            .as_deref()
+            .and_then(|p| fs::canonicalize(p).ok());
+        if current.as_ref() == Some(&file) || self.importing.contains(&file) {
+            return Err(import_error(format!("Circular import of {}", path)));
+        }
+
+        let src = fs::read_to_string(&file)
+            .map_err(|e| import_error(format!("Couldn't read {}: {}", path, e)))?;
+        // Static errors are reported as they're found, labelled with the imported file.
+        let error_reporter = ErrorReporter {
+            file_name: Some(path.clone()),
+            ..self.new_error_reporter()
+        };
+        let (tokens, error_reporter) = Scanner::new(src.clone(), error_reporter).scan_tokens();
+        let (stmts, error_reporter) = Parser::new(tokens, error_reporter).parse();
+        let (stmts, error_reporter) = Resolver::new(stmts, error_reporter).resolve();
+        if error_reporter.had_error {
+            self.static_errors(error_reporter);
+            return Err(import_error(format!(
+                "Couldn't import {} because of its errors",
+                path
+            )));
+        }
+        self.write_errors(&error_reporter.warnings);
+
+        // Functions declared in the imported file remember it as their source, so
+        // errors inside them are labelled with it wherever they're called from.
+        let source = Source {
+            name: Some(path.clone()),
+            text: src.into(),
+        };
+        let importing = self.importing.len();
+        self.importing.extend(current);
+        let importer = self.script_path.replace(file);
+        let importer_name = std::mem::replace(&mut self.source_name, source.name.clone());
+        let importer_source = std::mem::replace(&mut self.source, source.text.clone());
+        let mut environment = self.environment.clone();
+        let result = stmts
+            .iter()
+            .try_for_each(|stmt| self.execute(stmt, &mut environment).map(|_| ()));
+        self.source = importer_source;
+        self.source_name = importer_name;
+        self.script_path = importer;
+        self.importing.truncate(importing);
+
+        result.map_err(|e| import_error(e.in_source(&source).to_string()))
     }
 
     /// Executes a block statement
     pub fn execute_block(
         &mut self,
-        BlockStmt { body }: BlockStmt,
+        BlockStmt { body }: &BlockStmt,
         exec_env: &mut Environment,
-    ) -> Option<LoxObject> {
+    ) -> RuntimeResult<ControlFlow> {
         // In a new block scope
         exec_env.in_new_local_scope(|e| {
-            for stmt in body.into_iter() {
-                // Execute each statement in the block and return if necessary.
-                let maybe_return = self.execute(stmt, e);
-                if maybe_return.is_some() {
-                    return maybe_return;
+            for stmt in body.iter() {
+                // Execute each statement in the block and bail out if control flow changes.
+                let control_flow = self.execute(stmt, e)?;
+                if control_flow != ControlFlow::Normal {
+                    return Ok(control_flow);
                 }
             }
 
-            // Otherwise, no return value
-            None
+            // Otherwise, carry on
+            Ok(ControlFlow::Normal)
         })
     }
 
     /// Executes a variable declaration statement.
     fn variable_statement(
         &mut self,
-        VariableDeclarationStmt { name, initializer }: VariableDeclarationStmt,
+        VariableDeclarationStmt {
+            name,
+            initializer,
+            is_const,
+        }: &VariableDeclarationStmt,
         exec_env: &mut Environment,
     ) -> RuntimeResult<()> {
+        exec_env.check_redeclaration(name)?;
+
         // Evaluate the initializer if one was provided, or
         // default to nil.
         let value = initializer
+            .as_ref()
             .map(|expr| self.evaluate(expr, exec_env))
             .transpose()?
             .unwrap_or(LoxObject::Nil);
 
         // Define the variable in the current scope.
-        exec_env.define(&name.lexeme, value);
+        if *is_const {
+            exec_env.define_const(&name.lexeme, value);
+        } else {
+            exec_env.define(&name.lexeme, value);
+        }
         Ok(())
     }
 
+    /// Whether a value counts as true in a condition, taking `zero_is_falsy` into account.
+    pub fn is_truthy(&self, value: &LoxObject) -> bool {
+        if self.zero_is_falsy && *value == LoxObject::Number(0.0) {
+            return false;
+        }
+        value.is_truthy()
+    }
+
+    /// Evaluates the condition of an `if`, loop or ternary down to a bool.
+    fn evaluate_condition(
+        &mut self,
+        expr: &Expr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<bool> {
+        let value = self.evaluate(expr, exec_env)?;
+        Ok(self.is_truthy(&value))
+    }
+
     /// Top level function for evaluating an expression
-    fn evaluate(&mut self, expr: Expr, exec_env: &mut Environment) -> RuntimeResult<LoxObject> {
+    pub fn evaluate(
+        &mut self,
+        expr: &Expr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
         match expr {
             Expr::Binary(binary) => self.evaluate_binary(binary, exec_env),
 
             // For a grouping, just evaluate the inner expression.
-            Expr::Grouping(GroupingExpr { expr }) => self.evaluate(*expr, exec_env),
+            Expr::Grouping(GroupingExpr { expr }) => self.evaluate(expr, exec_env),
             Expr::Literal(literal) => Ok(self.evaluate_literal(literal)),
             Expr::Unary(unary) => self.evaluate_unary(unary, exec_env),
+            Expr::IncDec(inc_dec) => self.evaluate_inc_dec(inc_dec, exec_env),
 
-            // For a variable, just lookup the variable in the environment.
-            Expr::Variable(VariableExpr { name }) => exec_env.get(name),
+            // For a variable, look it up in the scope the resolver found it in.
+            Expr::Variable(VariableExpr { name, depth }) => exec_env.get_at(*depth, name),
             Expr::Assignment(assignment) => self.evaluate_assignment(assignment, exec_env),
             Expr::Logical(binary) => self.evaluate_logical_expression(binary, exec_env),
+            Expr::Ternary(TernaryExpr {
+                condition,
+                then_expr,
+                else_expr,
+            }) => {
+                if self.evaluate_condition(condition, exec_env)? {
+                    self.evaluate(then_expr, exec_env)
+                } else {
+                    self.evaluate(else_expr, exec_env)
+                }
+            }
             Expr::Call(call) => self.evaluate_call_expr(call, exec_env),
+            Expr::Get(get) => self.evaluate_get_expr(get, exec_env),
+            Expr::Set(set) => self.evaluate_set_expr(set, exec_env),
+
+            // `this` is just a variable bound when a method is accessed on an instance.
+            Expr::This(ThisExpr { keyword, depth }) => exec_env.get_at(*depth, keyword),
+            Expr::Interpolation(interpolation) => {
+                self.evaluate_interpolation_expr(interpolation, exec_env)
+            }
+            Expr::Array(array) => self.evaluate_array_expr(array, exec_env),
+            Expr::Index(index) => self.evaluate_index_expr(index, exec_env),
+            Expr::IndexSet(index_set) => self.evaluate_index_set_expr(index_set, exec_env),
+            Expr::Slice(slice) => self.evaluate_slice_expr(slice, exec_env),
+        }
+    }
+
+    /// Evaluates a string with embedded expressions, converting each value to a
+    /// string the same way `print` would.
+    fn evaluate_interpolation_expr(
+        &mut self,
+        InterpolationExpr { parts }: &InterpolationExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let mut s = String::new();
+        for part in parts {
+            s.push_str(&self.evaluate(part, exec_env)?.to_string());
+        }
+        Ok(LoxObject::String(s))
+    }
+
+    /// Evaluates an array literal into a new array.
+    fn evaluate_array_expr(
+        &mut self,
+        ArrayExpr { elements }: &ArrayExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let elements = elements
+            .iter()
+            .map(|element| self.evaluate(element, exec_env))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LoxObject::Array(Rc::new(RefCell::new(elements))))
+    }
+
+    /// Evaluates indexing into an array.
+    fn evaluate_index_expr(
+        &mut self,
+        IndexExpr {
+            object,
+            closing_bracket,
+            index,
+        }: &IndexExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let object = self.evaluate(object, exec_env)?;
+        let index = self.evaluate(index, exec_env)?;
+
+        if let LoxObject::Array(elements) = object {
+            let elements = elements.borrow();
+            let i = array_index(index, elements.len(), closing_bracket)?;
+            Ok(elements[i].clone())
+        } else {
+            Err(RuntimeError::new(
+                closing_bracket.clone(),
+                "Can only index into arrays.",
+            ))
+        }
+    }
+
+    /// Evaluates assigning to an index of an array. Like variable assignment,
+    /// evaluates to the new value of the element.
+    fn evaluate_index_set_expr(
+        &mut self,
+        IndexSetExpr {
+            object,
+            closing_bracket,
+            index,
+            value,
+        }: &IndexSetExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let object = self.evaluate(object, exec_env)?;
+        let index = self.evaluate(index, exec_env)?;
+        let value = self.evaluate(value, exec_env)?;
+
+        if let LoxObject::Array(elements) = object {
+            let mut elements = elements.borrow_mut();
+            let i = array_index(index, elements.len(), closing_bracket)?;
+            elements[i] = value.clone();
+            Ok(value)
+        } else {
+            Err(RuntimeError::new(
+                closing_bracket.clone(),
+                "Can only index into arrays.",
+            ))
+        }
+    }
+
+    /// Evaluates taking a slice of an array or string, which makes a new one. A missing
+    /// start or end means the start or end of the whole thing. Bounds past the end are
+    /// clamped to it, and a start after the end gives an empty slice. Like indexes, the
+    /// bounds have to be whole numbers, and can't be negative.
+    fn evaluate_slice_expr(
+        &mut self,
+        SliceExpr {
+            object,
+            closing_bracket,
+            start,
+            end,
+        }: &SliceExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let object = self.evaluate(object, exec_env)?;
+        let mut bound = |bound: &Option<Box<Expr>>| {
+            bound
+                .as_ref()
+                .map(|bound| {
+                    let bound = self.evaluate(bound, exec_env)?;
+                    slice_bound(bound, closing_bracket)
+                })
+                .transpose()
+        };
+        let start = bound(start)?.unwrap_or(0);
+        let end = bound(end)?;
+
+        match object {
+            LoxObject::Array(elements) => {
+                let elements = elements.borrow();
+                let end = end.unwrap_or(elements.len()).min(elements.len());
+                let slice = elements.get(start..end).unwrap_or_default().to_vec();
+                Ok(LoxObject::Array(Rc::new(RefCell::new(slice))))
+            }
+            LoxObject::String(s) => {
+                // Like `substring`, strings are sliced by characters rather than bytes.
+                let len = end.map_or(usize::MAX, |end| end.saturating_sub(start));
+                Ok(LoxObject::String(s.chars().skip(start).take(len).collect()))
+            }
+            _ => Err(RuntimeError::new(
+                closing_bracket.clone(),
+                "Can only slice arrays and strings.",
+            )),
+        }
+    }
+
+    /// Evaluates a property access expression.
+    fn evaluate_get_expr(
+        &mut self,
+        GetExpr { object, name }: &GetExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        match self.evaluate(object, exec_env)? {
+            // Getters run as soon as they're accessed, as if they'd been called.
+            LoxObject::Instance(instance) => match LoxInstance::getter(&instance, name) {
+                Some(getter) => self.call_function(&getter, vec![], name, exec_env),
+                None => LoxInstance::get(&instance, name),
+            },
+            LoxObject::Class(class) => class.get(name),
+
+            // Strings and arrays have a couple of built in properties, but no fields.
+            LoxObject::String(s) if name.lexeme == "length" => {
+                Ok(LoxObject::Number(s.chars().count() as f64))
+            }
+            LoxObject::Array(elements) if name.lexeme == "length" => {
+                Ok(LoxObject::Number(elements.borrow().len() as f64))
+            }
+            LoxObject::String(_) | LoxObject::Array(_) => Err(RuntimeError::new(
+                name.clone(),
+                format!("Undefined property '{}'.", name.lexeme),
+            )),
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances and classes have properties.",
+            )),
+        }
+    }
+
+    /// Evaluates a property assignment expression. Like variable assignment,
+    /// evaluates to the new value of the property.
+    fn evaluate_set_expr(
+        &mut self,
+        SetExpr {
+            object,
+            name,
+            value,
+        }: &SetExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        if let LoxObject::Instance(instance) = self.evaluate(object, exec_env)? {
+            let value = self.evaluate(value, exec_env)?;
+            instance.borrow_mut().set(name, value.clone());
+            Ok(value)
+        } else {
+            Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have fields.",
+            ))
         }
     }
 
@@ -236,92 +1247,174 @@ impl Interpreter {
     /// ```
     fn evaluate_assignment(
         &mut self,
-        AssignmentExpr { variable, expr }: AssignmentExpr,
+        AssignmentExpr {
+            variable,
+            expr,
+            depth,
+        }: &AssignmentExpr,
         exec_env: &mut Environment,
     ) -> RuntimeResult<LoxObject> {
         // Evaluate the expression
-        let value = self.evaluate(*expr, exec_env)?;
+        let value = self.evaluate(expr, exec_env)?;
 
         // Update the variable in the environment to be the value of the expression.
-        exec_env.assign(variable, value.clone())?;
+        exec_env.assign_at(*depth, variable, value.clone())?;
 
         // Return the new value
         Ok(value)
     }
 
+    /// Evaluates an increment or decrement, updating the variable and returning either
+    /// the new value (`++i`) or the old one (`i++`).
+    fn evaluate_inc_dec(
+        &mut self,
+        IncDecExpr {
+            variable,
+            operator,
+            is_prefix,
+            depth,
+        }: &IncDecExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let old = match exec_env.get_at(*depth, variable)? {
+            LoxObject::Number(n) => n,
+            _ => {
+                return Err(RuntimeError::new(
+                    operator.clone(),
+                    format!("Operand of `{}` must be a number", operator.lexeme),
+                ))
+            }
+        };
+
+        let new = if operator.token_type == TokenType::PlusPlus {
+            old + 1.0
+        } else {
+            old - 1.0
+        };
+        exec_env.assign_at(*depth, variable, LoxObject::Number(new))?;
+
+        Ok(LoxObject::Number(if *is_prefix { new } else { old }))
+    }
+
     /// Evaluates a call expression.
     fn evaluate_call_expr(
+        &mut self,
+        call: &CallExpr,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        let (function, args) = self.prepare_call(call, exec_env)?;
+        self.call_function(&*function, args, &call.closing_paren, exec_env)
+    }
+
+    /// Evaluates the callee and arguments of a call expression, checking that the
+    /// callee can be called with that many arguments.
+    fn prepare_call(
         &mut self,
         CallExpr {
             callee,
             closing_paren,
             args,
-        }: CallExpr,
+        }: &CallExpr,
         exec_env: &mut Environment,
-    ) -> RuntimeResult<LoxObject> {
+    ) -> RuntimeResult<(Box<dyn LoxCallable>, Vec<LoxObject>)> {
         // Lookup the function in the environment by evaluating the variable.
-        let callee = self.evaluate(*callee, exec_env)?;
+        let callee = self.evaluate(callee, exec_env)?;
 
         // Evaluate each argument of the function
         let args = args
-            .into_iter()
+            .iter()
             .map(|arg| self.evaluate(arg, exec_env))
             .collect::<Result<Vec<_>, _>>()?;
 
-        if let LoxObject::Function(function) = callee {
-            if args.len() != function.arity() {
-                Err(RuntimeError::new(
-                    closing_paren,
-                    format!(
-                        "Expect {} arguments but got {}",
-                        function.arity(),
-                        args.len()
-                    ),
+        // Functions and classes are the only callable objects
+        let function: Box<dyn LoxCallable> = match callee {
+            LoxObject::Function(function) => function,
+            LoxObject::Class(class) => Box::new(class),
+            _ => {
+                return Err(RuntimeError::new(
+                    closing_paren.clone(),
+                    "Can only call functions and classes.",
                 ))
-            } else {
-                Ok(function.call(self, exec_env, args))
             }
-        } else {
+        };
+
+        if !function.accepts(args.len()) {
             Err(RuntimeError::new(
-                closing_paren,
-                "Can only call functions and classes.",
+                closing_paren.clone(),
+                format!(
+                    "{} expects {} arguments but got {}",
+                    function.name(),
+                    function.expected_args(),
+                    args.len()
+                ),
             ))
+        } else {
+            Ok((function, args))
         }
     }
 
-    // Evaluates `and` and `or` expressions
+    /// Calls a function (or class) with arguments already checked by `prepare_call`.
+    fn call_function(
+        &mut self,
+        function: &dyn LoxCallable,
+        args: Vec<LoxObject>,
+        closing_paren: &Token,
+        exec_env: &mut Environment,
+    ) -> RuntimeResult<LoxObject> {
+        if self.call_depth >= self.max_call_depth {
+            Err(RuntimeError::new(closing_paren.clone(), "Stack overflow."))
+        } else {
+            self.call_depth += 1;
+            let result = function
+                .call(self, exec_env, args)
+                .map_err(|e| e.at_call_site(closing_paren));
+            self.call_depth -= 1;
+            result
+        }
+    }
+
+    /// Evaluates `and`, `or` and `??` expressions. These return one of their operands
+    /// as it is, rather than turning it into a boolean: whichever one decided the
+    /// result, which is the last one evaluated. So `"a" and "b"` is `"b"`, `nil and x`
+    /// is `nil`, `"a" or "b"` is `"a"`, and `nil or "b"` is `"b"`.
     fn evaluate_logical_expression(
         &mut self,
-        BinaryExpr { lhs, operator, rhs }: BinaryExpr,
+        BinaryExpr { lhs, operator, rhs }: &BinaryExpr,
         exec_env: &mut Environment,
     ) -> RuntimeResult<LoxObject> {
         // Evaluate the left side of the expression
-        let left = self.evaluate(*lhs, exec_env)?;
+        let left = self.evaluate(lhs, exec_env)?;
+
+        // If it's `true or something`, `false and something` or `value ?? something`,
+        // short circuit with the value on the left.
+        let short_circuits = match operator.token_type {
+            TokenType::QuestionQuestion => left != LoxObject::Nil,
+            TokenType::Or => self.is_truthy(&left),
+            _ => !self.is_truthy(&left),
+        };
 
-        // If it's `true or something` or `false and something`, short circuit
-        // with the value on the left.
-        if (operator.token_type == TokenType::Or && left.is_truthy()) || !left.is_truthy() {
+        if short_circuits {
             Ok(left)
         } else {
             // Doesn't short circuit, must evaluate rhs
-            self.evaluate(*rhs, exec_env)
+            self.evaluate(rhs, exec_env)
         }
     }
 
     /// Converts a unary expression into a LoxObject
     fn evaluate_unary(
         &mut self,
-        UnaryExpr { operator, rhs }: UnaryExpr,
+        UnaryExpr { operator, rhs }: &UnaryExpr,
         exec_env: &mut Environment,
     ) -> RuntimeResult<LoxObject> {
         // Evaluate the right hand side expression
-        let right = self.evaluate(*rhs, exec_env)?;
+        let right = self.evaluate(rhs, exec_env)?;
 
         match operator.token_type {
             TokenType::Bang => {
                 // !some_var should return a boolean based on whether the object
                 // conforms to Lox's conception of "truthiness"
-                Ok(LoxObject::Boolean(!right.is_truthy()))
+                Ok(LoxObject::Boolean(!self.is_truthy(&right)))
             }
             TokenType::Minus => {
                 // The unary minus negates a number, but for anything else produces
@@ -348,53 +1441,125 @@ impl Interpreter {
     /// Converts a binary expression into a LoxObject
     fn evaluate_binary(
         &mut self,
-        BinaryExpr { lhs, operator, rhs }: BinaryExpr,
+        BinaryExpr { lhs, operator, rhs }: &BinaryExpr,
         exec_env: &mut Environment,
     ) -> RuntimeResult<LoxObject> {
         // Evaluate the left and right expressions.
-        let left = self.evaluate(*lhs, exec_env)?;
-        let right = self.evaluate(*rhs, exec_env)?;
+        let left = self.evaluate(lhs, exec_env)?;
+        let right = self.evaluate(rhs, exec_env)?;
 
         match operator.token_type {
             TokenType::EqualEqual => Ok(LoxObject::Boolean(left == right)),
             TokenType::BangEqual => Ok(LoxObject::Boolean(left != right)),
 
             // The `+` operator adds numbers and concatenates strings in lox, so we
-            // handle both cases and error otherwise.
-            TokenType::Plus => {
-                if let (LoxObject::Number(l), LoxObject::Number(r)) = (&left, &right) {
-                    Ok(LoxObject::Number(l + r))
-                } else if let (LoxObject::String(mut l), LoxObject::String(r)) = (left, right) {
+            // handle both cases and error otherwise. A string and a number can only be
+            // concatenated if the interpreter has been told to coerce them.
+            TokenType::Plus => match (left, right) {
+                (LoxObject::Number(l), LoxObject::Number(r)) => Ok(LoxObject::Number(l + r)),
+                (LoxObject::String(mut l), LoxObject::String(r)) => {
                     l.push_str(&r);
                     Ok(LoxObject::String(l))
-                } else {
-                    Err(RuntimeError::new(
-                        operator,
-                        "Can only add number + number or concatenate string + string",
-                    ))
                 }
-            }
-            _ => {
-                // The rest of the operators only apply to numbers, so we can build the error
-                // and try to downcast the LoxObjects into f64s once, then apply them appropriately.
-                let error = RuntimeError::new(
+                (LoxObject::String(l), r @ LoxObject::Number(_)) if self.coerce_strings => {
+                    Ok(LoxObject::String(format!("{}{}", l, r)))
+                }
+                (l @ LoxObject::Number(_), LoxObject::String(r)) if self.coerce_strings => {
+                    Ok(LoxObject::String(format!("{}{}", l, r)))
+                }
+                _ => Err(RuntimeError::new(
+                    operator.clone(),
+                    "Can only add number + number or concatenate string + string",
+                )),
+            },
+
+            // Multiplying a string by a number repeats it, i.e. "ab" * 3 is "ababab".
+            TokenType::Star => match (left, right) {
+                (LoxObject::String(s), LoxObject::Number(n))
+                | (LoxObject::Number(n), LoxObject::String(s)) => {
+                    if n < 0.0 || n.fract() != 0.0 {
+                        return Err(RuntimeError::new(
+                            operator.clone(),
+                            format!(
+                                "Can only repeat a string a whole number of times, got {}",
+                                LoxObject::Number(n)
+                            ),
+                        ));
+                    }
+                    Ok(LoxObject::String(s.repeat(n as usize)))
+                }
+                (LoxObject::Number(l), LoxObject::Number(r)) => Ok(LoxObject::Number(l * r)),
+                _ => Err(RuntimeError::new(
+                    operator.clone(),
+                    "Can only multiply number * number or repeat string * number",
+                )),
+            },
+            // Checks the type of the left side against a name `type()` would give, i.e. `3 is "number"`.
+            TokenType::Is => match right {
+                LoxObject::String(type_name) => {
+                    Ok(LoxObject::Boolean(left.type_name() == type_name))
+                }
+                right => Err(RuntimeError::new(
                     operator.clone(),
                     format!(
-                        "Operator `{}` only applies to number types",
+                        "Can only check a value 'is' a type name string, not {}",
+                        right.type_name()
+                    ),
+                )),
+            },
+
+            // Numbers can be compared with numbers, and strings with strings (lexicographically).
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => match (left, right) {
+                (LoxObject::Number(l), LoxObject::Number(r)) => {
+                    Ok(LoxObject::Boolean(compare(&operator.token_type, l, r)))
+                }
+                (LoxObject::String(l), LoxObject::String(r)) => {
+                    Ok(LoxObject::Boolean(compare(&operator.token_type, l, r)))
+                }
+                _ => Err(RuntimeError::new(
+                    operator.clone(),
+                    format!(
+                        "Operator `{}` can only compare two numbers or two strings",
                         operator.lexeme
                     ),
-                );
-                let l = f64::try_from(left).map_err(|_| error.clone())?;
-                let r = f64::try_from(right).map_err(|_| error)?;
+                )),
+            },
+
+            _ => {
+                // The rest of the operators only apply to numbers, so we can describe the error
+                // and try to downcast the LoxObjects into f64s once, then apply them appropriately.
+                let error = || {
+                    RuntimeError::new(
+                        operator.clone(),
+                        format!(
+                            "Operator `{}` only applies to number types",
+                            operator.lexeme
+                        ),
+                    )
+                };
+                let l = f64::try_from(left).map_err(|_| error())?;
+                let r = f64::try_from(right).map_err(|_| error())?;
+
+                // Rust would happily give us inf or NaN here, which would then silently
+                // spread through the rest of the program.
+                if matches!(operator.token_type, TokenType::Slash | TokenType::Percent) && r == 0.0
+                {
+                    return Err(RuntimeError::new(operator.clone(), "Division by zero"));
+                }
 
                 match operator.token_type {
                     TokenType::Minus => Ok(LoxObject::Number(l - r)),
-                    TokenType::Star => Ok(LoxObject::Number(l * r)),
                     TokenType::Slash => Ok(LoxObject::Number(l / r)),
-                    TokenType::Greater => Ok(LoxObject::Boolean(l > r)),
-                    TokenType::GreaterEqual => Ok(LoxObject::Boolean(l >= r)),
-                    TokenType::Less => Ok(LoxObject::Boolean(l < r)),
-                    TokenType::LessEqual => Ok(LoxObject::Boolean(l <= r)),
+                    TokenType::Percent => Ok(LoxObject::Number(l % r)),
+                    TokenType::StarStar => Ok(LoxObject::Number(l.powf(r))),
+                    TokenType::Ampersand
+                    | TokenType::Pipe
+                    | TokenType::Caret
+                    | TokenType::LessLess
+                    | TokenType::GreaterGreater => bitwise(operator, l, r),
                     _ => {
                         // Error out at the end of the match
                         Err(RuntimeError::new(
@@ -410,10 +1575,10 @@ impl Interpreter {
     /// Transform an Expr::Literal's token into a LoxObject
     /// # Panics
     /// Panics if the token within the parse LiteralExpr is not a Literal
-    fn evaluate_literal(&self, LiteralExpr { token }: LiteralExpr) -> LoxObject {
-        match token.token_type {
-            TokenType::String(s) => LoxObject::String(s),
-            TokenType::Number(n) => LoxObject::Number(n),
+    fn evaluate_literal(&self, LiteralExpr { token }: &LiteralExpr) -> LoxObject {
+        match &token.token_type {
+            TokenType::String(s) => LoxObject::String(s.clone()),
+            TokenType::Number(n) => LoxObject::Number(*n),
             TokenType::True => LoxObject::Boolean(true),
             TokenType::False => LoxObject::Boolean(false),
             TokenType::Nil => LoxObject::Nil,
@@ -421,3 +1586,182 @@ impl Interpreter {
         }
     }
 }
+
+/// Checks that an index is a whole number within the bounds of an array of the given length,
+/// and converts it to a usize for indexing.
+fn array_index(index: LoxObject, len: usize, closing_bracket: &Token) -> RuntimeResult<usize> {
+    match index {
+        LoxObject::Number(n) if n.fract() != 0.0 => Err(RuntimeError::new(
+            closing_bracket.clone(),
+            format!("Array index must be a whole number, got {}", n),
+        )),
+        LoxObject::Number(n) if n < 0.0 || n >= len as f64 => Err(RuntimeError::new(
+            closing_bracket.clone(),
+            format!("Index {} out of bounds for array of length {}", n, len),
+        )),
+        LoxObject::Number(n) => Ok(n as usize),
+        _ => Err(RuntimeError::new(
+            closing_bracket.clone(),
+            "Array index must be a number.",
+        )),
+    }
+}
+
+/// Checks that a slice bound is a whole, non-negative number, and converts it to a usize.
+/// Unlike an index it can be past the end, since slices are clamped.
+fn slice_bound(bound: LoxObject, closing_bracket: &Token) -> RuntimeResult<usize> {
+    match bound {
+        LoxObject::Number(n) if n.fract() != 0.0 || n < 0.0 => Err(RuntimeError::new(
+            closing_bracket.clone(),
+            format!(
+                "Slice bounds must be whole, non-negative numbers, got {}",
+                n
+            ),
+        )),
+        LoxObject::Number(n) => Ok(n as usize),
+        _ => Err(RuntimeError::new(
+            closing_bracket.clone(),
+            "Slice bounds must be numbers.",
+        )),
+    }
+}
+
+/// Whether a `break` or `continue` with the given target applies to a loop with the
+/// given label. One without a target applies to the innermost loop, whatever its label.
+fn is_aimed_at(target: &Option<String>, label: &Option<Token>) -> bool {
+    match (target, label) {
+        (None, _) => true,
+        (Some(target), Some(label)) => *target == label.lexeme,
+        (Some(_), None) => false,
+    }
+}
+
+/// Reads the system clock, in seconds since the unix epoch (negative if the clock is
+/// somehow set before it).
+fn system_clock() -> f64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(time) => time.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Applies one of the ordering operators (`>`, `>=`, `<`, `<=`) to two values.
+fn compare<T: PartialOrd>(operator: &TokenType, l: T, r: T) -> bool {
+    match operator {
+        TokenType::Greater => l > r,
+        TokenType::GreaterEqual => l >= r,
+        TokenType::Less => l < r,
+        _ => l <= r,
+    }
+}
+
+/// Applies one of the bitwise operators (`&`, `|`, `^`, `<<`, `>>`) to two whole numbers,
+/// working on them as 64 bit integers.
+fn bitwise(operator: &Token, l: f64, r: f64) -> RuntimeResult<LoxObject> {
+    let as_integer = |n: f64| {
+        if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+            Ok(n as i64)
+        } else {
+            Err(RuntimeError::new(
+                operator.clone(),
+                format!(
+                    "Operator `{}` only applies to whole numbers, got {}",
+                    operator.lexeme,
+                    LoxObject::Number(n)
+                ),
+            ))
+        }
+    };
+    let (l, r) = (as_integer(l)?, as_integer(r)?);
+
+    let result = match operator.token_type {
+        TokenType::Ampersand => l & r,
+        TokenType::Pipe => l | r,
+        TokenType::Caret => l ^ r,
+        _ => {
+            if !(0..64).contains(&r) {
+                return Err(RuntimeError::new(
+                    operator.clone(),
+                    format!("Can only shift by 0 to 63 bits, got {}", r),
+                ));
+            }
+            if operator.token_type == TokenType::LessLess {
+                l << r
+            } else {
+                l >> r
+            }
+        }
+    };
+    Ok(LoxObject::Number(result as f64))
+}
+
+/// Decides whether a line typed into the REPL should be run as a bare expression rather
+/// than as statements, going by how it starts and ends.
+fn is_bare_expression(tokens: &[Token]) -> bool {
+    let starts_statement = matches!(
+        tokens.first().map(|token| &token.token_type),
+        Some(
+            TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::Const
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Do
+                | TokenType::Foreach
+                | TokenType::Repeat
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try
+                | TokenType::Import
+                | TokenType::Break
+                | TokenType::Continue
+                | TokenType::LeftBrace
+        )
+    );
+
+    // The last token is always an EOF, so look at the one before it.
+    let ends_statement = matches!(
+        tokens.iter().rev().nth(1).map(|token| &token.token_type),
+        Some(TokenType::SemiColon | TokenType::RightBrace) | None
+    );
+
+    !starts_statement && !ends_statement
+}
+
+/// Some source code along with what to call it in error messages, usually the name
+/// of the file it came from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Source {
+    pub name: Option<String>,
+    pub text: Rc<str>,
+}
+
+/// An in memory output that can be handed to the interpreter while we keep a
+/// handle to read back what was written to it.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Everything written so far, as a string.
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
 pub mod bubble_closure;
 pub mod builtin_functions;
 pub mod callable;
+pub mod class;
 pub mod environment;
 pub mod error;
 pub mod function;
 pub mod grammar;
+pub mod instance;
 pub mod interpreter;
 pub mod lox;
 pub mod object;
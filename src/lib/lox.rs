@@ -1,11 +1,96 @@
 use std::env;
 use std::fs;
-use std::io::{self, BufRead, Write};
+use std::io::{BufRead, Write};
 
-use crate::error::error_reporter::ErrorReporter;
-use crate::interpreter::Interpreter;
-use crate::parser::Parser;
-use crate::scanner::Scanner;
+use crate::interpreter::{Interpreter, SharedBuffer};
+
+/// Scans, parses, and interprets some Lox source code in a fresh interpreter, without ever
+/// exiting the process. Every error reported along the way is collected into the `Err`.
+/// # Example
+/// ```
+/// use rust_lox_impl::lox::run_source;
+///
+/// let errors = run_source("print(1 +);").unwrap_err();
+/// assert_eq!(errors, vec!["[Line 1, Col 10] Error at ')': Expected Expression"]);
+///
+/// assert!(run_source("var three = 1 + 2;").is_ok());
+/// ```
+pub fn run_source(src: &str) -> Result<(), Vec<String>> {
+    Interpreter::new().run_source(src)
+}
+
+/// The command line arguments the interpreter was started with.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Args {
+    /// The script to run. The REPL is started if there isn't one.
+    pub script: Option<String>,
+
+    /// Print the tokens the scanner produces before running anything.
+    pub dump_tokens: bool,
+
+    /// Print the syntax tree the parser produces before running anything.
+    pub dump_ast: bool,
+
+    /// Let `+` concatenate strings with numbers.
+    pub coerce_strings: bool,
+
+    /// Treat `0` as falsy.
+    pub zero_is_falsy: bool,
+}
+
+impl Args {
+    /// Parses the command line arguments (not including the program name). Flags can go
+    /// anywhere, and whatever's left over is the script. Returns `None` if there's an
+    /// unknown flag or more than one script.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::lox::Args;
+    ///
+    /// let args = Args::parse(vec!["--dump-tokens".to_owned(), "script.lox".to_owned()]).unwrap();
+    /// assert!(args.dump_tokens);
+    /// assert!(!args.dump_ast);
+    /// assert_eq!(args.script, Some("script.lox".to_owned()));
+    /// ```
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        let mut parsed = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "--dump-tokens" => parsed.dump_tokens = true,
+                "--dump-ast" => parsed.dump_ast = true,
+                "--coerce-strings" => parsed.coerce_strings = true,
+                "--zero-is-falsy" => parsed.zero_is_falsy = true,
+                flag if flag.starts_with("--") => return None,
+                _ if parsed.script.is_some() => return None,
+                _ => parsed.script = Some(arg),
+            }
+        }
+        Some(parsed)
+    }
+}
+
+/// How running some Lox code turned out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The code ran to completion.
+    Ok,
+
+    /// The code was rejected before it ran, by the scanner, parser, or resolver.
+    StaticError,
+
+    /// The code started running but hit an error part way through.
+    RuntimeError,
+}
+
+impl Outcome {
+    /// The conventional exit code for the outcome (from sysexits.h, like the book uses).
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Ok => 0,
+            Outcome::StaticError => 65,
+            Outcome::RuntimeError => 70,
+        }
+    }
+}
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Mode {
@@ -30,26 +115,43 @@ impl Lox {
 
     /// Handles parsing the command line arguments for the interpreter.
     pub fn lox_main(&mut self) {
-        let args: Vec<String> = env::args().collect();
-
-        if args.len() == 1 {
-            // Running the executable with no arguments starts the repl
-            self.run_prompt();
-        } else if args.len() == 2 {
-            // Running the executable with a single argument runs the provided filename as lox code
-            self.run_file(&args[1]);
-        } else {
-            println!("Usage: jlox [script])");
-            std::process::exit(64);
+        // Skip the program name
+        let args = match Args::parse(env::args().skip(1)) {
+            Some(args) => args,
+            None => {
+                println!("Usage: jlox [--dump-tokens] [--dump-ast] [--coerce-strings] [--zero-is-falsy] [script]");
+                std::process::exit(64);
+            }
+        };
+
+        self.interpreter.dump_tokens = args.dump_tokens;
+        self.interpreter.dump_ast = args.dump_ast;
+        self.interpreter.coerce_strings = args.coerce_strings;
+        self.interpreter.zero_is_falsy = args.zero_is_falsy;
+
+        match args.script {
+            // Running the executable with a filename runs the file as lox code
+            Some(script) => self.run_file(&script),
+
+            // Running the executable without one starts the repl
+            None => self.run_prompt(),
         }
     }
 
-    /// Reads the contents of a file as a string and passes it to the run function.
+    /// Reads the contents of a file as a string and passes it to the run function,
+    /// exiting with the conventional error code if running it fails.
     fn run_file(&mut self, filename: &str) {
         self.mode = Some(Mode::Script);
+        self.interpreter.source_name = Some(filename.to_owned());
+        self.interpreter.script_path = Some(filename.into());
         let file_contents = fs::read_to_string(filename);
         match file_contents {
-            Ok(code) => self.run(code),
+            Ok(code) => {
+                let outcome = self.run(&code);
+                if outcome != Outcome::Ok {
+                    std::process::exit(outcome.exit_code());
+                }
+            }
             Err(e) => {
                 eprintln!(
                     "Error attempting to run code in file {}. Associated error: {}",
@@ -62,65 +164,113 @@ impl Lox {
     /// Passes stdin to the run function line by line.
     fn run_prompt(&mut self) {
         self.mode = Some(Mode::Repl);
-        let mut repl_active = true;
+        self.read_eval_print_loop();
+    }
 
-        while repl_active {
-            // This print macro really ought to flush stdout
-            print!("> ");
-            io::stdout().flush().expect("Couldn't flush stdout");
+    /// Runs a REPL over the given input instead of stdin. Each line is run as it's read,
+    /// until the input runs out or a line is `:exit` or `:quit`. Prompts (and anything the
+    /// code prints) go to the interpreter's output.
+    ///
+    /// The input also becomes the interpreter's input, so the `input` builtin reads from
+    /// the same lines as the REPL does.
+    pub fn repl(&mut self, input: impl BufRead + 'static) {
+        self.mode = Some(Mode::Repl);
+        self.interpreter.input = Box::new(input);
+        self.read_eval_print_loop();
+    }
 
-            // Read in the line from stdin
-            let stdin = io::stdin();
-            let line = stdin.lock().lines().next().expect("No line provided");
+    /// Runs a REPL over the given input like `repl`, returning everything it wrote
+    /// (prompts included) rather than printing it.
+    /// # Example
+    /// ```
+    /// use std::io::Cursor;
+    /// use rust_lox_impl::lox::Lox;
+    ///
+    /// // Nothing after `:quit` is run.
+    /// let output = Lox::new().repl_to_string(Cursor::new("print(1);\n:quit\nprint(2);\n"));
+    /// assert_eq!(output, "> 1\n> ");
+    ///
+    /// // Running out of input ends the REPL too, finishing the prompt line.
+    /// let output = Lox::new().repl_to_string(Cursor::new("print(1);\n"));
+    /// assert_eq!(output, "> 1\n> \n");
+    ///
+    /// // A line that's just an expression has its value printed.
+    /// let output = Lox::new().repl_to_string(Cursor::new("3 * 4\n"));
+    /// assert_eq!(output, "> 12\n> \n");
+    /// ```
+    pub fn repl_to_string(&mut self, input: impl BufRead + 'static) -> String {
+        let buffer = SharedBuffer::default();
+        let output = std::mem::replace(&mut self.interpreter.output, Box::new(buffer.clone()));
+        self.repl(input);
+        self.interpreter.output = output;
+        buffer.contents()
+    }
 
-            match line {
-                // Run th eprovided line of code if there is one
-                Ok(code) if !code.is_empty() => self.run(code),
+    /// Reads lines from the interpreter's input and runs them until told to stop.
+    fn read_eval_print_loop(&mut self) {
+        loop {
+            // The prompt has no newline, so it needs an explicit flush
+            write!(self.interpreter.output, "> ").expect("Couldn't write prompt");
+            self.interpreter
+                .output
+                .flush()
+                .expect("Couldn't flush output");
 
-                // Print an error if we get one while trying to read in the line
-                Err(_) => {
-                    eprintln!("Error reading line from terminal");
+            let mut line = String::new();
+            match self.interpreter.input.read_line(&mut line) {
+                // The input ran out (Ctrl-D in a terminal). Finish the prompt line
+                // so the shell doesn't start on the same one.
+                Ok(0) => {
+                    writeln!(self.interpreter.output).expect("Couldn't write output");
+                    break;
                 }
+                Ok(_) => match line.trim() {
+                    ":exit" | ":quit" => break,
+                    "" => continue,
+                    code => {
+                        // Errors have already been reported, and the REPL carries on regardless.
+                        self.run(code);
+                    }
+                },
 
-                // This means is was an empty line, so we deactivate the repl
-                _ => {
-                    repl_active = false;
+                // Print an error if we get one while trying to read in the line
+                Err(e) => {
+                    eprintln!("Error reading line from terminal: {}", e);
+                    break;
                 }
             }
         }
     }
 
-    /// Takes the code through each step of the lifecycle (scanning, parsing, ...)
-    fn run(&mut self, src: String) {
-        let debug_mode = false;
-
-        let error_reporter = ErrorReporter::new();
-
-        // Scan the source code into a list of Tokens
-        let scanner = Scanner::new(src, error_reporter);
-        let (tokens, error_reporter) = scanner.scan_tokens();
-        if debug_mode {
-            println!("Tokens: {:?}", tokens);
-        }
+    /// Runs the code and reports how it went. Errors are printed as they're found,
+    /// but it's left to the caller to decide what to do about them.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::lox::{Lox, Outcome};
+    ///
+    /// let mut lox = Lox::new();
+    /// assert_eq!(lox.run("var a = 1;"), Outcome::Ok);
+    /// assert_eq!(lox.run("var b = ;"), Outcome::StaticError);
+    /// assert_eq!(lox.run("nil();"), Outcome::RuntimeError);
+    /// ```
+    pub fn run(&mut self, src: &str) -> Outcome {
+        let result = match self.mode {
+            Some(Mode::Repl) => self.interpreter.run_repl_line(src),
+            _ => self.interpreter.run_source(src),
+        };
 
-        // Parse the Tokens into a list of statements.
-        let parser = Parser::new(tokens, error_reporter);
-        let (stmts, error_reporter) = parser.parse();
-        if debug_mode {
-            println!("Syntax Tree: {:?}", stmts.clone());
-        }
+        match result {
+            Ok(()) => Outcome::Ok,
 
-        // Exit if there were static errors
-        if error_reporter.had_error && self.mode == Some(Mode::Script) {
-            std::process::exit(65);
+            // Only runtime errors end up in the interpreter's error reporter.
+            Err(_) if self.interpreter.error_reporter.had_error => Outcome::RuntimeError,
+            Err(_) => Outcome::StaticError,
         }
+    }
+}
 
-        // Use the Tree Walk Interpreter to evaluate the statements.
-        self.interpreter.interpret(stmts.clone());
-
-        // Exit if there were Runtime errors
-        if self.interpreter.error_reporter.had_error && self.mode == Some(Mode::Script) {
-            std::process::exit(70);
-        }
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
     }
 }
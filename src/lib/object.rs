@@ -1,4 +1,6 @@
-use crate::callable::LoxCallable;
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{callable::LoxCallable, class::LoxClass, instance::LoxInstance};
 
 /// The job of this enum is essentially to map Lox Objects to Rust types. It is our replacement
 /// for the use of java.lang.Object in the Interpreter.
@@ -9,8 +11,17 @@ pub enum LoxObject {
     Boolean(bool),
     Nil,
     Function(Box<dyn LoxCallable>),
+    Class(LoxClass),
+    Instance(Rc<RefCell<LoxInstance>>),
+
+    /// Arrays are reference types, so assigning an array to a new variable
+    /// doesn't copy it.
+    Array(Rc<RefCell<Vec<LoxObject>>>),
 }
 
+/// What an array shares between everything holding it.
+type ArrayCell = RefCell<Vec<LoxObject>>;
+
 impl PartialEq for LoxObject {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -23,13 +34,63 @@ impl PartialEq for LoxObject {
             // equivalent, functions enclose different environments, so semantically
             // in Lox they should never be the same.
             (Self::Function(_), Self::Function(_)) => false,
+
+            // Instances and arrays are reference types, so they are only equal if they
+            // are the same instance or array. See `LoxObject::equals` for comparing contents.
+            (Self::Class(l), Self::Class(r)) => l == r,
+            (Self::Instance(l), Self::Instance(r)) => Rc::ptr_eq(l, r),
+            (Self::Array(l), Self::Array(r)) => Rc::ptr_eq(l, r),
             _ => false,
         }
     }
 }
 
 impl LoxObject {
-    /// Function casts a LoxObject to a bool
+    /// Compares two objects by value, the way the `equals` builtin does. Arrays are equal
+    /// if their elements are, all the way down, and everything else compares like `==`.
+    pub fn equals(&self, other: &Self) -> bool {
+        self.equals_visiting(other, &mut HashSet::new())
+    }
+
+    /// `equals`, remembering which pairs of arrays are already being compared. Meeting
+    /// a pair again means the arrays contain themselves, and since nothing has differed
+    /// so far, the pair is taken to be equal rather than compared forever.
+    fn equals_visiting(
+        &self,
+        other: &Self,
+        visited: &mut HashSet<(*const ArrayCell, *const ArrayCell)>,
+    ) -> bool {
+        match (self, other) {
+            (Self::Array(l), Self::Array(r)) => {
+                if Rc::ptr_eq(l, r) || !visited.insert((Rc::as_ptr(l), Rc::as_ptr(r))) {
+                    return true;
+                }
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len()
+                    && l.iter()
+                        .zip(r.iter())
+                        .all(|(l, r)| l.equals_visiting(r, visited))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// The name of the type of the object, as exposed to Lox code by the `type` builtin.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxObject::String(_) => "string",
+            LoxObject::Number(_) => "number",
+            LoxObject::Boolean(_) => "boolean",
+            LoxObject::Nil => "nil",
+            LoxObject::Function(_) => "function",
+            LoxObject::Class(_) => "class",
+            LoxObject::Instance(_) => "instance",
+            LoxObject::Array(_) => "array",
+        }
+    }
+
+    /// Function casts a LoxObject to a bool. Like in the book, only `nil` and `false`
+    /// are falsy. See `Interpreter::zero_is_falsy` for the older rule where `0` is too.
     pub fn is_truthy(&self) -> bool {
         match self {
             // Boolean is its own value
@@ -38,15 +99,33 @@ impl LoxObject {
             // Nil is False
             LoxObject::Nil => false,
 
-            // Zero is false
-            LoxObject::Number(n) => *n != 0f64,
-
             // Everything else is true
             _ => true,
         }
     }
 }
 
+/// Formats a number the way jlox does, so whole numbers print without a decimal
+/// point. Everything else is rounded to 15 significant digits, which hides float
+/// noise like `0.1 + 0.2` printing as `0.30000000000000004`.
+pub fn format_number(n: f64) -> String {
+    if !n.is_finite() || n == 0.0 {
+        return format!("{}", n);
+    }
+    if n.fract() == 0.0 {
+        return format!("{:.0}", n);
+    }
+
+    // How many digits we have left for after the decimal point.
+    let int_digits = n.abs().log10().floor() as i32 + 1;
+    let decimals = (15 - int_digits).max(0) as usize;
+    let formatted = format!("{:.*}", decimals, n);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
 /// Implemented this as a convenience. I'm willing to bet there's
 /// a crate aimed at deriving these for all associated values,
 /// like a sort of cast for enums.
@@ -69,7 +148,7 @@ impl std::fmt::Display for LoxObject {
                 write!(f, "{}", s)
             }
             LoxObject::Number(n) => {
-                write!(f, "{}", n)
+                write!(f, "{}", format_number(*n))
             }
             LoxObject::Boolean(b) => {
                 write!(f, "{}", b)
@@ -80,6 +159,50 @@ impl std::fmt::Display for LoxObject {
             LoxObject::Function(function) => {
                 write!(f, "{}", function)
             }
+            LoxObject::Class(class) => {
+                write!(f, "{}", class)
+            }
+            LoxObject::Instance(instance) => {
+                write!(f, "{}", instance.borrow())
+            }
+            LoxObject::Array(elements) => fmt_array(f, elements, &mut HashSet::new()),
+        }
+    }
+}
+
+/// Writes out an array and everything in it. `formatting` holds the arrays we're already
+/// in the middle of writing, so an array that contains itself is written as `[...]`
+/// the second time round rather than forever.
+fn fmt_array(
+    f: &mut std::fmt::Formatter,
+    elements: &Rc<RefCell<Vec<LoxObject>>>,
+    formatting: &mut HashSet<*const ArrayCell>,
+) -> std::fmt::Result {
+    if !formatting.insert(Rc::as_ptr(elements)) {
+        return write!(f, "[...]");
+    }
+
+    write!(f, "[")?;
+    for (i, element) in elements.borrow().iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match element {
+            LoxObject::Array(inner) => fmt_array(f, inner, formatting)?,
+            other => write!(f, "{}", other)?,
+        }
+    }
+    formatting.remove(&Rc::as_ptr(elements));
+    write!(f, "]")
+}
+
+/// Shows strings quoted so they can be told apart from other values, and everything
+/// else the same as `Display`.
+impl std::fmt::Debug for LoxObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoxObject::String(s) => write!(f, "{:?}", s),
+            other => write!(f, "{}", other),
         }
     }
 }
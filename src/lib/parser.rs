@@ -3,10 +3,14 @@ use crate::{
     error::{
         error_reporter::ErrorReporter,
         parse_error::{ParseError, ParseErrorCtx},
+        warning::Warning,
     },
     grammar::{
-        AssignmentExpr, BinaryExpr, BlockStmt, CallExpr, Expr, ExpressionStmt,
-        FunctionDeclarationStmt, GroupingExpr, IfStmt, LiteralExpr, ReturnStmt, Stmt, UnaryExpr,
+        ArrayExpr, AssignmentExpr, BinaryExpr, BlockStmt, BreakStmt, CallExpr,
+        ClassDeclarationStmt, ContinueStmt, DoWhileStmt, Expr, ExpressionStmt, ForEachStmt,
+        FunctionDeclarationStmt, GetExpr, GroupingExpr, IfStmt, ImportStmt, IncDecExpr, IndexExpr,
+        IndexSetExpr, InterpolationExpr, LiteralExpr, Param, RepeatStmt, ReturnStmt, SetExpr,
+        SliceExpr, Stmt, TernaryExpr, ThisExpr, ThrowStmt, TryStmt, UnaryExpr,
         VariableDeclarationStmt, VariableExpr, WhileStmt,
     },
     token::{Token, TokenType},
@@ -14,6 +18,28 @@ use crate::{
 
 pub type ParseResult<T> = Result<T, ParseError>;
 
+/// Parses tokens into statements, with a fresh error reporter holding any errors
+/// found along the way.
+/// # Example
+/// ```
+/// use rust_lox_impl::{grammar::Stmt, parser::parse, scanner::scan};
+///
+/// let (tokens, _) = scan("1 + 2;");
+/// let (stmts, error_reporter) = parse(tokens);
+/// assert!(matches!(stmts.as_slice(), [Stmt::Expression(_)]));
+/// assert!(!error_reporter.had_error);
+///
+/// // Nothing after a return in the same block can run, which gets a warning
+/// // pointing at the first statement that can't.
+/// let (tokens, _) = scan("fun f() {\n  return 1;\n  var x = 2;\n  print(x);\n}");
+/// let (_, error_reporter) = parse(tokens);
+/// assert_eq!(error_reporter.warnings, vec!["[Line 3, Col 3] Warning at 'var': Code after 'return' can never run"]);
+/// assert!(!error_reporter.had_error);
+/// ```
+pub fn parse(tokens: Vec<Token>) -> (Vec<Stmt>, ErrorReporter) {
+    Parser::new(tokens, ErrorReporter::new()).parse()
+}
+
 /// The parser is responsible for taking a list of tokens and turning them into a syntax tree.
 pub struct Parser {
     /// The list of tokens to parse into a syntax tree
@@ -26,7 +52,19 @@ pub struct Parser {
     error_reporter: ErrorReporter,
 
     /// Used to keep track of how many local scopes deep we are (for variable resolving)
-    depth: usize
+    depth: usize,
+
+    /// Used to keep track of how many loops deep we are, so that `break` and `continue`
+    /// can be rejected outside of a loop.
+    loop_depth: usize,
+
+    /// The labels of the loops we're inside of, so that `break` and `continue`
+    /// can be checked against them.
+    loop_labels: Vec<String>,
+
+    /// Used to keep track of how many functions deep we are, so that `return`
+    /// can be rejected outside of a function.
+    function_depth: usize,
 }
 
 impl Parser {
@@ -35,25 +73,67 @@ impl Parser {
             tokens,
             current: 0,
             error_reporter,
-            depth: 0
+            depth: 0,
+            loop_depth: 0,
+            loop_labels: vec![],
+            function_depth: 0,
         }
     }
 
     /// Parses the provided list of Tokens into Lox Statements.
     /// Uses go style tuple error return so that multiple
     /// errors can be collected.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{error::error_reporter::ErrorReporter, parser::Parser, scanner::Scanner};
+    ///
+    /// let src = "var a = ;\nprint(1, 2 *, 3);\n{ a = 1 + ; }\nprint(a);";
+    /// let (tokens, error_reporter) = Scanner::new(src.to_owned(), ErrorReporter::new()).scan_tokens();
+    /// let (stmts, error_reporter) = Parser::new(tokens, error_reporter).parse();
+    /// assert_eq!(
+    ///     error_reporter.messages,
+    ///     vec![
+    ///         "[Line 1, Col 9] Error at ';': Expected Expression",
+    ///         "[Line 2, Col 13] Error at ',': Expected Expression",
+    ///         "[Line 3, Col 11] Error at ';': Expected Expression",
+    ///     ]
+    /// );
+    /// assert_eq!(stmts.len(), 3);
+    /// ```
     pub fn parse(mut self) -> (Vec<Stmt>, ErrorReporter) {
         let mut statements = vec![];
+        let mut warned = false;
 
         while !self.is_at_end() {
-            if let Some(stmt) = self.parse_item() {
-                statements.push(stmt)
-            }
+            self.parse_item_into(&mut statements, &mut warned);
         }
 
         (statements, self.error_reporter)
     }
 
+    /// Parses the provided list of Tokens as a single expression, like a line typed
+    /// into the REPL without a trailing semicolon.
+    pub fn parse_expression(mut self) -> (Option<Expr>, ErrorReporter) {
+        let expr = self.expression().and_then(|expr| {
+            if self.is_at_end() {
+                Ok(expr)
+            } else {
+                Err(ParseError::ExpectedDifferentToken(
+                    self.err_ctx(),
+                    TokenType::SemiColon,
+                ))
+            }
+        });
+
+        match expr {
+            Ok(expr) => (Some(expr), self.error_reporter),
+            Err(e) => {
+                self.error_reporter.error(e);
+                (None, self.error_reporter)
+            }
+        }
+    }
+
     /// Tries to parse a single statement, and returns the statement if succsessful
     /// or reports an error and syncronizes the parser.
     fn parse_item(&mut self) -> Option<Stmt> {
@@ -65,26 +145,102 @@ impl Parser {
             .ok()
     }
 
+    /// Parses the next item of a list of statements (a block or the whole program)
+    /// onto the end of it.
+    ///
+    /// Anything after a statement that always jumps away is dead code, which is
+    /// almost certainly a mistake. It gets a warning pointing at the start of the
+    /// first statement that can't run, once per list, tracked by `warned`.
+    fn parse_item_into(&mut self, statements: &mut Vec<Stmt>, warned: &mut bool) {
+        let start = self.current_token();
+        let Some(stmt) = self.parse_item() else {
+            return;
+        };
+
+        // Only the statement right after the first jump in the list gets the warning.
+        if !*warned {
+            if let Some(keyword) = statements.last().and_then(jump_keyword) {
+                let keyword = keyword.lexeme.clone();
+                self.error_reporter
+                    .warning(Warning::UnreachableCode(start.into(), keyword));
+                *warned = true;
+            }
+        }
+        statements.push(stmt);
+    }
+
     /// A declaration is the top level parsable entity. Tries to parse
-    /// a function declaration or a variable declaration, or defaults
+    /// a class, function, or variable declaration, or defaults
     /// to some other kind of statement.
     fn declaration(&mut self) -> ParseResult<Stmt> {
-        if self.advance_on(TokenType::Fun) {
-            self.function_declaration()
-                .map(|stmt| Stmt::FunctionDeclaration(stmt))
+        if self.advance_on(TokenType::Class) {
+            self.class_declaration().map(Stmt::ClassDeclaration)
+        } else if self.advance_on(TokenType::Fun) {
+            self.function_declaration().map(Stmt::FunctionDeclaration)
         } else if self.advance_on(TokenType::Var) {
-            self.var_declaration()
-                .map(|stmt| Stmt::VariableDeclaration(stmt))
+            self.var_declaration(false).map(Stmt::VariableDeclaration)
+        } else if self.advance_on(TokenType::Const) {
+            self.var_declaration(true).map(Stmt::VariableDeclaration)
         } else {
             self.statement()
         }
     }
 
-    /// Parses a function declaration statement. Triggered when a `fun` token is
+    /// Parses a class declaration statement. Triggered when a `class` token is
     /// encountered.
-    fn function_declaration(&mut self) -> ParseResult<FunctionDeclarationStmt> {
+    fn class_declaration(&mut self) -> ParseResult<ClassDeclarationStmt> {
+        let name = self.advance_on_or_err(TokenType::Identifier)?;
+        self.advance_on_or_err(TokenType::LeftBrace)?;
+
+        // Methods are declared like functions, just without the `fun` keyword.
+        // A method without a parameter list is a getter, and one with a `class`
+        // prefix is called on the class rather than an instance.
+        let mut methods = vec![];
+        let mut getters = vec![];
+        let mut class_methods = vec![];
+        while !self.is_at_end() && !self.current_token_is_a(TokenType::RightBrace) {
+            if self.advance_on(TokenType::Class) {
+                // `class fun make()` reads fine too, so the `fun` is allowed.
+                self.advance_on(TokenType::Fun);
+                class_methods.push(self.function_declaration()?);
+            } else if self.current_token_is_a(TokenType::Identifier)
+                && self.next_token_is_a(TokenType::LeftBrace)
+            {
+                getters.push(self.getter_declaration()?);
+            } else {
+                methods.push(self.function_declaration()?);
+            }
+        }
 
+        self.advance_on_or_err(TokenType::RightBrace)?;
+        Ok(ClassDeclarationStmt {
+            name,
+            methods,
+            getters,
+            class_methods,
+        })
+    }
 
+    /// Parses a getter in a class body, which is a method with a name and a body
+    /// but no parameter list.
+    /// ```lox
+    /// area {
+    ///     return this.width * this.height;
+    /// }
+    /// ```
+    fn getter_declaration(&mut self) -> ParseResult<FunctionDeclarationStmt> {
+        let name = self.advance_on_or_err(TokenType::Identifier)?;
+        let body = self.function_body()?;
+        Ok(FunctionDeclarationStmt {
+            name,
+            params: vec![],
+            body,
+        })
+    }
+
+    /// Parses a function declaration statement. Triggered when a `fun` token is
+    /// encountered (or for a method in a class body).
+    fn function_declaration(&mut self) -> ParseResult<FunctionDeclarationStmt> {
         // Parse the function name and the opening parenthesis.
         let name = self.advance_on_or_err(TokenType::Identifier)?;
         let left_paren = self.advance_on_or_err(TokenType::LeftParen)?;
@@ -93,11 +249,14 @@ impl Parser {
         let mut params = vec![];
         if !self.current_token_is_a(TokenType::RightParen) {
             // Parse the first parameter.
-            params.push(self.advance_on_or_err(TokenType::Identifier)?);
+            params.push(self.parameter(&params)?);
 
-            // Parse the comma and the next parameter if there is one.
-            while self.advance_on(TokenType::Comma) {
-                params.push(self.advance_on_or_err(TokenType::Identifier)?);
+            // Parse the comma and the next parameter if there is one. A trailing
+            // comma before the closing paren is allowed.
+            while self.advance_on(TokenType::Comma)
+                && !self.current_token_is_a(TokenType::RightParen)
+            {
+                params.push(self.parameter(&params)?);
 
                 // We set a rule that functions can have no more than 255 parameters.
                 if params.len() >= 255 {
@@ -111,17 +270,67 @@ impl Parser {
 
         // Consume the closing parenthesis and the body of the function as a block statement
         self.advance_on_or_err(TokenType::RightParen)?;
-        self.advance_on_or_err(TokenType::LeftBrace)?;
-
-        
-        let body = self.block_statement()?.body;
+        let body = self.function_body()?;
 
         // Return the function declaration.
         Ok(FunctionDeclarationStmt { name, params, body })
     }
 
-    /// Parses a variable declaration. Triggered when a `var` keyword is encountered.
-    fn var_declaration(&mut self) -> ParseResult<VariableDeclarationStmt> {
+    /// Parses the body of a function (or method) as a block statement.
+    fn function_body(&mut self) -> ParseResult<Vec<Stmt>> {
+        self.advance_on_or_err(TokenType::LeftBrace)?;
+
+        // A loop surrounding the function declaration doesn't make `break` valid
+        // inside the function body.
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_loop_labels = std::mem::take(&mut self.loop_labels);
+        self.function_depth += 1;
+        let body = self.block_statement();
+        self.function_depth -= 1;
+        self.loop_depth = enclosing_loop_depth;
+        self.loop_labels = enclosing_loop_labels;
+        Ok(body?.body)
+    }
+
+    /// Parses a single function parameter, along with its default value if it has one.
+    /// Once one parameter has a default, all of the ones after it need one too.
+    /// A rest parameter (`...name`) can't have a default and has to come last.
+    fn parameter(&mut self, previous: &[Param]) -> ParseResult<Param> {
+        if let Some(rest) = previous.last().filter(|param| param.is_rest) {
+            self.error_reporter
+                .error(ParseError::RestParamNotLast(rest.name.clone().into()));
+        }
+
+        if self.advance_on(TokenType::DotDotDot) {
+            let name = self.advance_on_or_err(TokenType::Identifier)?;
+            return Ok(Param {
+                name,
+                default: None,
+                is_rest: true,
+            });
+        }
+
+        let name = self.advance_on_or_err(TokenType::Identifier)?;
+        let default = if self.advance_on(TokenType::Equal) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        if default.is_none() && previous.iter().any(|param| param.default.is_some()) {
+            self.error_reporter
+                .error(ParseError::RequiredParamAfterDefault(name.clone().into()));
+        }
+
+        Ok(Param {
+            name,
+            default,
+            is_rest: false,
+        })
+    }
+
+    /// Parses a variable declaration. Triggered when a `var` or `const` keyword is encountered.
+    fn var_declaration(&mut self, is_const: bool) -> ParseResult<VariableDeclarationStmt> {
         // Parse the variable name
         let name = self.advance_on_or_err(TokenType::Identifier)?;
 
@@ -131,40 +340,105 @@ impl Parser {
             .advance_on(TokenType::Equal)
             .then_try(|| self.expression())?;
 
+        // A constant can never be assigned later, so it has to get its value now.
+        if is_const && initializer.is_none() {
+            return Err(ParseError::ConstWithoutInitializer(name.into()));
+        }
+
         // Consume the semi-colon to end the statement
         self.advance_on_or_err(TokenType::SemiColon)?;
 
         // Return the variable declaration.
-        Ok(VariableDeclarationStmt { name, initializer })
+        Ok(VariableDeclarationStmt {
+            name,
+            initializer,
+            is_const,
+        })
     }
 
     /// Handles statements which are not declarations.
     fn statement(&mut self) -> ParseResult<Stmt> {
-        if self.advance_on(TokenType::If) {
-            self.if_statement().map(|stmt| Stmt::If(stmt))
-        } else if self.advance_on(TokenType::For) {
-            // the for statement desugars to multiple wrapped
-            // statements, which we handle in the function.
-            self.for_statement()
-        } else if self.advance_on(TokenType::While) {
-            self.while_statement().map(|stmt| Stmt::While(stmt))
+        if self.current_token_is_a(TokenType::Identifier) && self.next_token_is_a(TokenType::Colon)
+        {
+            self.labelled_statement()
+        } else if let Some(loop_stmt) = self.loop_statement(None) {
+            loop_stmt
+        } else if self.advance_on(TokenType::If) {
+            self.if_statement().map(Stmt::If)
         } else if self.advance_on(TokenType::Return) {
-            self.return_statement().map(|stmt| Stmt::Return(stmt))
+            self.return_statement().map(Stmt::Return)
+        } else if self.advance_on(TokenType::Break) {
+            self.break_statement().map(Stmt::Break)
+        } else if self.advance_on(TokenType::Continue) {
+            self.continue_statement().map(Stmt::Continue)
         } else if self.advance_on(TokenType::LeftBrace) {
-            self.block_statement().map(|stmt| Stmt::Block(stmt))
+            self.block_statement().map(Stmt::Block)
+        } else if self.advance_on(TokenType::Try) {
+            self.try_statement().map(Stmt::Try)
+        } else if self.advance_on(TokenType::Throw) {
+            self.throw_statement().map(Stmt::Throw)
+        } else if self.advance_on(TokenType::Import) {
+            self.import_statement().map(Stmt::Import)
         } else {
             self.expression_statement()
         }
     }
 
-    /// Parses a for loop, and creates a desugared while loop representation
+    /// Parses a loop if the current token starts one, giving it the label (if any).
+    /// Returns None if the current token doesn't start a loop.
+    fn loop_statement(&mut self, label: Option<Token>) -> Option<ParseResult<Stmt>> {
+        let loop_stmt = if self.advance_on(TokenType::For) {
+            // the for statement desugars to multiple wrapped
+            // statements, which we handle in the function.
+            self.for_statement(label)
+        } else if self.advance_on(TokenType::While) {
+            self.while_statement(label).map(Stmt::While)
+        } else if self.advance_on(TokenType::Foreach) {
+            self.foreach_statement(label).map(Stmt::ForEach)
+        } else if self.advance_on(TokenType::Repeat) {
+            self.repeat_statement(label).map(Stmt::Repeat)
+        } else if self.advance_on(TokenType::Do) {
+            self.do_while_statement(label).map(Stmt::DoWhile)
+        } else {
+            return None;
+        };
+        Some(loop_stmt)
+    }
+
+    /// Parses a loop with a label in front of it, so that a `break` or `continue`
+    /// in a nested loop can refer to it.
+    /// ```lox
+    /// outer: while (true) {
+    ///     while (true) {
+    ///         break outer;
+    ///     }
+    /// }
     /// ```
+    fn labelled_statement(&mut self) -> ParseResult<Stmt> {
+        let label = self.advance();
+        self.advance_on_or_err(TokenType::Colon)?;
+
+        self.loop_labels.push(label.lexeme.clone());
+        let loop_stmt = self.loop_statement(Some(label.clone()));
+        self.loop_labels.pop();
+
+        // Anything else is reported, but still parsed as if it had no label.
+        // We report the error but we dont throw it because we dont need to synchronize.
+        loop_stmt.unwrap_or_else(|| {
+            self.error_reporter
+                .error(ParseError::LabelOnNonLoop(label.into()));
+            self.statement()
+        })
+    }
+
+    /// Parses a for loop, and creates a desugared while loop representation
+    /// ```lox
     /// for (var i = 1; i <= 10; i = i + 1) {
     ///     print(i);
     /// }
     /// ```
     /// caramalizes to
-    /// ```
+    /// ```lox
     /// {
     ///     var i = 1;
     ///     while(i <= 10) {
@@ -173,14 +447,16 @@ impl Parser {
     ///     }
     /// }
     /// ```
-    fn for_statement(&mut self) -> ParseResult<Stmt> {
+    /// (The increment is actually stored on the while loop rather than appended to
+    /// the body, so that a `continue` doesn't skip it.)
+    fn for_statement(&mut self, label: Option<Token>) -> ParseResult<Stmt> {
         self.advance_on_or_err(TokenType::LeftParen)?;
 
         // Parse the initializer
         let initializer = if self.advance_on(TokenType::SemiColon) {
             None
         } else if self.advance_on(TokenType::Var) {
-            Some(Stmt::VariableDeclaration(self.var_declaration()?))
+            Some(Stmt::VariableDeclaration(self.var_declaration(false)?))
         } else {
             Some(self.expression_statement()?)
         };
@@ -202,26 +478,22 @@ impl Parser {
         self.advance_on_or_err(TokenType::RightParen)?;
 
         // Parse the body of the loop
-        let mut body = self.statement()?;
-
-        // Add the inc as a final statement to execute in the desugared while loop
-        if let Some(inc) = increment {
-            body = Stmt::Block(BlockStmt {
-                body: vec![body, Stmt::Expression(ExpressionStmt { expr: inc })],
-            });
-        }
+        let body = self.loop_body()?;
 
         // If the condition is null, set it to a simple literal true value.
         if condition.is_none() {
             condition = Some(Expr::Literal(LiteralExpr {
-                token: Token::new(TokenType::True, "true".to_owned(), 0),
+                token: Token::new(TokenType::True, "true".to_owned(), 0, 0),
             }))
         }
 
-        // Make the body a while loop which executes itself based on the condition
-        body = Stmt::While(WhileStmt {
+        // Make the body a while loop which executes itself based on the condition,
+        // running the inc after each iteration.
+        let mut body = Stmt::While(WhileStmt {
             condition: condition.unwrap(),
             body: Box::new(body),
+            increment,
+            label,
         });
 
         // Make the body a block stmt which includes the initializer and the while loop
@@ -235,20 +507,156 @@ impl Parser {
     }
 
     /// Parses a while loop
-    fn while_statement(&mut self) -> ParseResult<WhileStmt> {
+    fn while_statement(&mut self, label: Option<Token>) -> ParseResult<WhileStmt> {
         self.advance_on_or_err(TokenType::LeftParen)?;
         let condition = self.expression()?;
         self.advance_on_or_err(TokenType::RightParen)?;
-        let body = self.statement()?;
+        let body = self.loop_body()?;
         Ok(WhileStmt {
             condition,
             body: Box::new(body),
+            increment: None,
+            label,
+        })
+    }
+
+    /// Parses a foreach loop
+    /// ```lox
+    /// foreach (x in [1, 2, 3]) {
+    ///     print(x);
+    /// }
+    /// ```
+    fn foreach_statement(&mut self, label: Option<Token>) -> ParseResult<ForEachStmt> {
+        self.advance_on_or_err(TokenType::LeftParen)?;
+        let variable = self.advance_on_or_err(TokenType::Identifier)?;
+        let in_keyword = self.advance_on_or_err(TokenType::In)?;
+        let iterable = self.expression()?;
+        self.advance_on_or_err(TokenType::RightParen)?;
+        let body = self.loop_body()?;
+        Ok(ForEachStmt {
+            variable,
+            in_keyword,
+            iterable,
+            body: Box::new(body),
+            label,
+        })
+    }
+
+    /// Parses a do while loop
+    /// ```lox
+    /// do {
+    ///     i = i + 1;
+    /// } while (i < 10);
+    /// ```
+    fn do_while_statement(&mut self, label: Option<Token>) -> ParseResult<DoWhileStmt> {
+        let body = self.loop_body()?;
+        self.advance_on_or_err(TokenType::While)?;
+        self.advance_on_or_err(TokenType::LeftParen)?;
+        let condition = self.expression()?;
+        self.advance_on_or_err(TokenType::RightParen)?;
+        self.advance_on_or_err(TokenType::SemiColon)?;
+        Ok(DoWhileStmt {
+            body: Box::new(body),
+            condition,
+            label,
+        })
+    }
+
+    /// Parses a repeat loop
+    /// ```lox
+    /// repeat (3) {
+    ///     print("hi");
+    /// }
+    /// ```
+    fn repeat_statement(&mut self, label: Option<Token>) -> ParseResult<RepeatStmt> {
+        let keyword = self.previous_token();
+        self.advance_on_or_err(TokenType::LeftParen)?;
+        let count = self.expression()?;
+        self.advance_on_or_err(TokenType::RightParen)?;
+        let body = self.loop_body()?;
+        Ok(RepeatStmt {
+            keyword,
+            count,
+            body: Box::new(body),
+            label,
         })
     }
 
+    /// Parses the body of a loop, keeping track of the fact that we're inside a loop.
+    fn loop_body(&mut self) -> ParseResult<Stmt> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parses a break statement, with an optional label
+    fn break_statement(&mut self) -> ParseResult<BreakStmt> {
+        let keyword = self.loop_control_keyword();
+        let label = self.loop_control_label();
+        self.advance_on_or_err(TokenType::SemiColon)?;
+        Ok(BreakStmt { keyword, label })
+    }
+
+    /// Parses a continue statement, with an optional label
+    fn continue_statement(&mut self) -> ParseResult<ContinueStmt> {
+        let keyword = self.loop_control_keyword();
+        let label = self.loop_control_label();
+        self.advance_on_or_err(TokenType::SemiColon)?;
+        Ok(ContinueStmt { keyword, label })
+    }
+
+    /// Parses the label after a `break`/`continue`, if there is one, reporting an error
+    /// if none of the loops we're in has that label.
+    fn loop_control_label(&mut self) -> Option<Token> {
+        if !self.advance_on(TokenType::Identifier) {
+            return None;
+        }
+        let label = self.previous_token();
+
+        // Outside of a loop, the keyword itself has already been reported.
+        if self.loop_depth > 0 && !self.loop_labels.contains(&label.lexeme) {
+            self.error_reporter.error(ParseError::UndefinedLabel(
+                label.clone().into(),
+                label.lexeme.clone(),
+            ));
+        }
+        Some(label)
+    }
+
+    /// Returns the `break`/`continue` keyword just consumed, reporting an error if we aren't in a loop.
+    /// We report the error but we dont throw it because we dont need to synchronize.
+    fn loop_control_keyword(&mut self) -> Token {
+        let keyword = self.previous_token();
+        if self.loop_depth == 0 {
+            self.error_reporter.error(ParseError::OutsideOfLoop(
+                keyword.clone().into(),
+                keyword.token_type.clone(),
+            ));
+        }
+        keyword
+    }
+
     /// Parse a return statement
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{parser::parse, scanner::scan};
+    ///
+    /// let (tokens, _) = scan("return 1;");
+    /// let (_, error_reporter) = parse(tokens);
+    /// assert_eq!(error_reporter.messages, vec!["[Line 1, Col 1] Error at 'return': Cannot use 'return' outside of a function"]);
+    ///
+    /// let (tokens, _) = scan("fun one() { return 1; } one();");
+    /// assert!(!parse(tokens).1.had_error);
+    /// ```
     fn return_statement(&mut self) -> ParseResult<ReturnStmt> {
         let return_keyword = self.previous_token();
+        if self.function_depth == 0 {
+            self.error_reporter.error(ParseError::ReturnOutsideFunction(
+                return_keyword.clone().into(),
+            ));
+        }
+
         let mut value = None;
         if !self.current_token_is_a(TokenType::SemiColon) {
             value = Some(self.expression()?);
@@ -266,10 +674,13 @@ impl Parser {
         let condition = self.expression()?;
         self.advance_on_or_err(TokenType::RightParen)?;
         let then_branch = Box::new(self.statement()?);
+
+        // An inner if without braces parses first and grabs the else for itself,
+        // which is how `if (a) if (b) x; else y;` ends up with the else on `if (b)`.
         let else_branch = self
             .advance_on(TokenType::Else)
             .then_try(|| self.statement())?
-            .map(|stmt| Box::new(stmt));
+            .map(Box::new);
         Ok(IfStmt {
             condition,
             then_branch,
@@ -277,15 +688,58 @@ impl Parser {
         })
     }
 
+    /// Parses a try statement. Both bodies have to be blocks.
+    /// try_stmt -> "try" block "catch" "(" IDENTIFIER ")" block
+    fn try_statement(&mut self) -> ParseResult<TryStmt> {
+        self.advance_on_or_err(TokenType::LeftBrace)?;
+        let body = self.block_statement()?;
+
+        self.advance_on_or_err(TokenType::Catch)?;
+        self.advance_on_or_err(TokenType::LeftParen)?;
+        let catch_variable = self.advance_on_or_err(TokenType::Identifier)?;
+        self.advance_on_or_err(TokenType::RightParen)?;
+
+        self.advance_on_or_err(TokenType::LeftBrace)?;
+        let catch_body = self.block_statement()?;
+
+        Ok(TryStmt {
+            body,
+            catch_variable,
+            catch_body,
+        })
+    }
+
+    /// Parses a throw statement.
+    /// throw_stmt -> "throw" expression ";"
+    fn throw_statement(&mut self) -> ParseResult<ThrowStmt> {
+        let keyword = self.previous_token();
+        let value = self.expression()?;
+        self.advance_on_or_err(TokenType::SemiColon)?;
+        Ok(ThrowStmt { keyword, value })
+    }
+
+    /// Parses an import statement. The file name has to be a plain string, since it's
+    /// needed before anything runs.
+    /// import_stmt -> "import" STRING ";"
+    fn import_statement(&mut self) -> ParseResult<ImportStmt> {
+        let keyword = self.previous_token();
+        let path = match self.current_token().token_type {
+            TokenType::String(path) => path,
+            _ => return Err(ParseError::ExpectedImportPath(self.err_ctx())),
+        };
+        self.advance();
+        self.advance_on_or_err(TokenType::SemiColon)?;
+        Ok(ImportStmt { keyword, path })
+    }
+
     /// Parses a block stmt
     fn block_statement(&mut self) -> ParseResult<BlockStmt> {
         self.depth += 1;
         let mut statements = vec![];
+        let mut warned = false;
 
         while !self.is_at_end() && !self.current_token_is_a(TokenType::RightBrace) {
-            if let Some(stmt) = self.parse_item() {
-                statements.push(stmt);
-            }
+            self.parse_item_into(&mut statements, &mut warned);
         }
 
         self.advance_on_or_err(TokenType::RightBrace)?;
@@ -302,28 +756,117 @@ impl Parser {
 
     /// expression -> assignment
     fn expression(&mut self) -> ParseResult<Expr> {
-        Ok(self.assignment()?)
+        self.assignment()
     }
 
-    /// assignment -> some_var = assignment
+    /// assignment -> ( call . )? some_var = assignment
+    ///             | call [ expression ] = assignment
     ///             | equality
     fn assignment(&mut self) -> ParseResult<Expr> {
-        // If we're looking as an assignment, this will trickle down to an Expr::Variable
-        let expr = self.or()?;
+        // If we're looking as an assignment, this will trickle down to an Expr::Variable (or Expr::Get/Expr::Index)
+        let expr = self.ternary()?;
 
         if self.advance_on(TokenType::Equal) {
             let equals = self.previous_token();
             let value = self.assignment()?;
 
-            if let Expr::Variable(VariableExpr { name }) = expr {
-                return Ok(Expr::Assignment(AssignmentExpr {
+            return match expr {
+                Expr::Variable(VariableExpr { name, .. }) => Ok(Expr::Assignment(AssignmentExpr {
                     variable: name,
                     expr: Box::new(value),
-                }));
-            }
+                    depth: None,
+                })),
+                Expr::Get(GetExpr { object, name }) => Ok(Expr::Set(SetExpr {
+                    object,
+                    name,
+                    value: Box::new(value),
+                })),
+                Expr::Index(IndexExpr {
+                    object,
+                    closing_bracket,
+                    index,
+                }) => Ok(Expr::IndexSet(IndexSetExpr {
+                    object,
+                    closing_bracket,
+                    index,
+                    value: Box::new(value),
+                })),
+                expr => {
+                    self.error_reporter
+                        .error(ParseError::InvalidAssignmentTarget(equals.into()));
+                    Ok(expr)
+                }
+            };
+        }
 
-            self.error_reporter
-                .error(ParseError::InvalidAssignmentTarget(equals.into()))
+        // Compound assignment, i.e. `x += 1` is just shorthand for `x = x + 1`.
+        if self.advance_on_any_of(vec![
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let compound_operator = self.previous_token();
+            let value = self.assignment()?;
+
+            return match expr {
+                Expr::Variable(VariableExpr { name, .. }) => {
+                    let operator = binary_operator_of(compound_operator);
+                    Ok(Expr::Assignment(AssignmentExpr {
+                        variable: name.clone(),
+                        expr: Box::new(Expr::Binary(BinaryExpr {
+                            lhs: Box::new(Expr::Variable(VariableExpr { name, depth: None })),
+                            operator,
+                            rhs: Box::new(value),
+                        })),
+                        depth: None,
+                    }))
+                }
+                expr => {
+                    self.error_reporter
+                        .error(ParseError::InvalidAssignmentTarget(
+                            compound_operator.into(),
+                        ));
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// ternary -> coalesce ( "?" expression ":" ternary )?
+    /// The else branch recurses so that ternaries chain to the right.
+    fn ternary(&mut self) -> ParseResult<Expr> {
+        let condition = self.coalesce()?;
+
+        if self.advance_on(TokenType::Question) {
+            let then_expr = self.expression()?;
+            self.advance_on_or_err(TokenType::Colon)?;
+            let else_expr = self.ternary()?;
+            return Ok(Expr::Ternary(TernaryExpr {
+                condition: Box::new(condition),
+                then_expr: Box::new(then_expr),
+                else_expr: Box::new(else_expr),
+            }));
+        }
+
+        Ok(condition)
+    }
+
+    /// Parses a nil coalescing expression, which binds looser than `or`.
+    /// coalesce -> or ( "??" or )*
+    fn coalesce(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.or()?;
+
+        while self.advance_on(TokenType::QuestionQuestion) {
+            let operator = self.previous_token();
+            let right = self.or()?;
+            expr = Expr::Logical(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(right),
+            });
         }
 
         Ok(expr)
@@ -378,15 +921,98 @@ impl Parser {
         Ok(expr)
     }
 
-    /// comparison -> term (( > | >= | < | <= ) term)*
+    /// comparison -> bit_or (( > | >= | < | <= | is ) bit_or)*
+    ///
+    /// The grammar allows `a < b < c`, but it would compare the boolean `a < b` to `c`,
+    /// which is never what was meant, so it gets reported (without needing to synchronize).
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{parser::parse, scanner::scan};
+    ///
+    /// let (tokens, _) = scan("print(1 < 2 < 3);");
+    /// let (_, error_reporter) = parse(tokens);
+    /// assert_eq!(error_reporter.messages, vec!["[Line 1, Col 13] Error at '<': Comparisons can't be chained, use parentheses or 'and' instead (like 'a < b and b < c')"]);
+    ///
+    /// let (tokens, _) = scan("print((1 < 2) == (2 < 3));");
+    /// assert!(!parse(tokens).1.had_error);
+    /// ```
     fn comparison(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.term()?;
+        let mut expr = self.bit_or()?;
+        let mut is_comparison = false;
         while self.advance_on_any_of(vec![
             TokenType::GreaterEqual,
             TokenType::Greater,
             TokenType::LessEqual,
             TokenType::Less,
+            TokenType::Is,
         ]) {
+            let operator = self.previous_token();
+            if is_comparison {
+                self.error_reporter
+                    .error(ParseError::ChainedComparison(operator.clone().into()));
+            }
+            is_comparison = true;
+            let right = self.bit_or()?;
+            expr = Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    /// The bitwise operators bind tighter than comparisons (like in Rust, rather than C),
+    /// so `x & 1 == 0` means `(x & 1) == 0`.
+    /// bit_or -> bit_xor ( | bit_xor )*
+    fn bit_or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_xor()?;
+        while self.advance_on(TokenType::Pipe) {
+            let operator = self.previous_token();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    /// bit_xor -> bit_and ( ^ bit_and )*
+    fn bit_xor(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_and()?;
+        while self.advance_on(TokenType::Caret) {
+            let operator = self.previous_token();
+            let right = self.bit_and()?;
+            expr = Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    /// bit_and -> shift ( & shift )*
+    fn bit_and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.shift()?;
+        while self.advance_on(TokenType::Ampersand) {
+            let operator = self.previous_token();
+            let right = self.shift()?;
+            expr = Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    /// shift -> term (( << | >> ) term)*
+    fn shift(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.term()?;
+        while self.advance_on_any_of(vec![TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.previous_token();
             let right = self.term()?;
             expr = Expr::Binary(BinaryExpr {
@@ -413,12 +1039,12 @@ impl Parser {
         Ok(expr)
     }
 
-    /// factor -> unary (( / | * ) unary)*
+    /// factor -> power (( / | * | % ) power)*
     fn factor(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.unary()?;
-        while self.advance_on_any_of(vec![TokenType::Slash, TokenType::Star]) {
+        let mut expr = self.power()?;
+        while self.advance_on_any_of(vec![TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous_token();
-            let right = self.unary()?;
+            let right = self.power()?;
             expr = Expr::Binary(BinaryExpr {
                 lhs: Box::new(expr),
                 operator,
@@ -428,8 +1054,25 @@ impl Parser {
         Ok(expr)
     }
 
+    /// Exponents are right associative, so `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    /// power -> unary ( ** power )?
+    fn power(&mut self) -> ParseResult<Expr> {
+        let expr = self.unary()?;
+        if self.advance_on(TokenType::StarStar) {
+            let operator = self.previous_token();
+            let right = self.power()?;
+            return Ok(Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(right),
+            }));
+        }
+        Ok(expr)
+    }
+
     /// unary -> ( ! | - ) unary
-    ///        | primary ;
+    ///        | ( ++ | -- ) unary
+    ///        | postfix ;
     fn unary(&mut self) -> ParseResult<Expr> {
         if self.advance_on_any_of(vec![TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous_token();
@@ -438,22 +1081,67 @@ impl Parser {
                 operator,
                 rhs: Box::new(right),
             }))
+        } else if self.advance_on_any_of(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous_token();
+            let target = self.unary()?;
+            Ok(self.inc_dec(target, operator, true))
+        } else {
+            self.postfix()
+        }
+    }
+
+    /// postfix -> call ( ++ | -- )? ;
+    fn postfix(&mut self) -> ParseResult<Expr> {
+        let expr = self.call()?;
+
+        if self.advance_on_any_of(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous_token();
+            Ok(self.inc_dec(expr, operator, false))
         } else {
-            Ok(self.call()?)
+            Ok(expr)
         }
     }
 
-    /// Parses a function call expression
+    /// Builds an increment or decrement of `target`. Like assignment, only a variable
+    /// can be incremented, so anything else is reported as an invalid target.
+    fn inc_dec(&mut self, target: Expr, operator: Token, is_prefix: bool) -> Expr {
+        match target {
+            Expr::Variable(VariableExpr { name, .. }) => Expr::IncDec(IncDecExpr {
+                variable: name,
+                operator,
+                is_prefix,
+                depth: None,
+            }),
+            expr => {
+                self.error_reporter
+                    .error(ParseError::InvalidAssignmentTarget(operator.into()));
+                expr
+            }
+        }
+    }
+
+    /// Parses a function call, property access, or index expression
     fn call(&mut self) -> ParseResult<Expr> {
         let mut expr = self.primary()?;
 
-        // We loop to support multiple calls for functions that produce functions
+        // We loop to support multiple calls for functions that produce functions,
+        // and chains of property accesses
         // ```
         // iProduceAFunc()();
+        // person.address.street;
+        // grid[0][1];
         // ```
         loop {
             if self.advance_on(TokenType::LeftParen) {
                 expr = self.finish_call(expr)?;
+            } else if self.advance_on(TokenType::Dot) {
+                let name = self.advance_on_or_err(TokenType::Identifier)?;
+                expr = Expr::Get(GetExpr {
+                    object: Box::new(expr),
+                    name,
+                });
+            } else if self.advance_on(TokenType::LeftBracket) {
+                expr = self.finish_index(expr)?;
             } else {
                 break;
             }
@@ -462,20 +1150,49 @@ impl Parser {
         Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
-        let mut args = vec![];
-        if !self.current_token_is_a(TokenType::RightParen) {
-            args.push(self.expression()?);
-            while self.advance_on(TokenType::Comma) {
-                if args.len() >= 255 {
-                    // We report an error but we dont throw it because we dont need to synchronize.
-                    self.error_reporter
-                        .error(ParseError::TooManyFunctionArguments(self.err_ctx()));
-                }
-                args.push(self.expression()?);
+    /// Parses the rest of an index or a slice, after the opening bracket. A colon
+    /// makes it a slice, and the expressions either side of it are optional.
+    /// index -> "[" expression "]"
+    /// slice -> "[" expression? ":" expression? "]"
+    fn finish_index(&mut self, object: Expr) -> ParseResult<Expr> {
+        let start = if self.current_token_is_a(TokenType::Colon) {
+            None
+        } else {
+            let index = self.expression()?;
+            if !self.current_token_is_a(TokenType::Colon) {
+                let closing_bracket = self.advance_on_or_err(TokenType::RightBracket)?;
+                return Ok(Expr::Index(IndexExpr {
+                    object: Box::new(object),
+                    closing_bracket,
+                    index: Box::new(index),
+                }));
             }
-        }
+            Some(Box::new(index))
+        };
+        self.advance_on_or_err(TokenType::Colon)?;
+
+        let end = (!self.current_token_is_a(TokenType::RightBracket))
+            .then_try(|| self.expression())?
+            .map(Box::new);
+        let closing_bracket = self.advance_on_or_err(TokenType::RightBracket)?;
+        Ok(Expr::Slice(SliceExpr {
+            object: Box::new(object),
+            closing_bracket,
+            start,
+            end,
+        }))
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let args = self.expression_list(TokenType::RightParen)?;
         let closing_paren = self.advance_on_or_err(TokenType::RightParen)?;
+        if args.len() > 255 {
+            // We report an error but we dont throw it because we dont need to synchronize.
+            self.error_reporter
+                .error(ParseError::TooManyFunctionArguments(
+                    closing_paren.clone().into(),
+                ));
+        }
         Ok(Expr::Call(CallExpr {
             callee: Box::new(callee),
             closing_paren,
@@ -483,12 +1200,18 @@ impl Parser {
         }))
     }
 
-    /// primary -> NUMBER | STRING | true | false | nil
-    ///          | ( expression )
+    /// primary -> NUMBER | STRING | true | false | nil | this
+    ///          | ( expression ) | [ ( expression ( , expression )* ,? )? ]
     fn primary(&mut self) -> ParseResult<Expr> {
         if self.advance_on(TokenType::Identifier) {
             Ok(Expr::Variable(VariableExpr {
                 name: self.previous_token(),
+                depth: None,
+            }))
+        } else if self.advance_on(TokenType::This) {
+            Ok(Expr::This(ThisExpr {
+                keyword: self.previous_token(),
+                depth: None,
             }))
         } else if self.advance_on(TokenType::LeftParen) {
             // Handle a grouping
@@ -497,6 +1220,11 @@ impl Parser {
             Ok(Expr::Grouping(GroupingExpr {
                 expr: Box::new(expr),
             }))
+        } else if self.advance_on(TokenType::LeftBracket) {
+            // Handle an array literal
+            let elements = self.expression_list(TokenType::RightBracket)?;
+            self.advance_on_or_err(TokenType::RightBracket)?;
+            Ok(Expr::Array(ArrayExpr { elements }))
         } else if self.advance_on_any_of(vec![TokenType::True, TokenType::False, TokenType::Nil]) {
             // Handle bool or nil
             Ok(Expr::Literal(LiteralExpr {
@@ -512,6 +1240,8 @@ impl Parser {
                 Ok(Expr::Literal(LiteralExpr {
                     token: self.advance(),
                 }))
+            } else if let TokenType::Interpolation(_) = self.current_token().token_type {
+                self.interpolation().map(Expr::Interpolation)
             } else {
                 // We've reached the bottom of the grammar and we don't know what expression this is.
                 // println!("{}: {}", self.previous_token(), self.previous_token().token_type);
@@ -520,6 +1250,39 @@ impl Parser {
         }
     }
 
+    /// Parses a string with embedded expressions. The scanner splits it up into an
+    /// `Interpolation` token for the text before each embedded expression, the tokens
+    /// of the expression itself, and then a plain `String` token for the rest.
+    fn interpolation(&mut self) -> ParseResult<InterpolationExpr> {
+        let mut parts = vec![];
+        loop {
+            // The text before an embedded expression.
+            let text = self.advance();
+            if let TokenType::Interpolation(s) = text.token_type.clone() {
+                parts.push(Expr::Literal(LiteralExpr {
+                    token: Token {
+                        token_type: TokenType::String(s),
+                        ..text
+                    },
+                }));
+            }
+
+            parts.push(self.expression()?);
+
+            // Either another embedded expression follows or the string ends.
+            match self.current_token().token_type {
+                TokenType::Interpolation(_) => continue,
+                TokenType::String(_) => {
+                    parts.push(Expr::Literal(LiteralExpr {
+                        token: self.advance(),
+                    }));
+                    return Ok(InterpolationExpr { parts });
+                }
+                _ => return Err(ParseError::UnterminatedInterpolation(self.err_ctx())),
+            }
+        }
+    }
+
     /// Will advance the current token if it has the given token type, otherwise
     /// it will produce an error with the given message.
     fn advance_on_or_err(&mut self, tt: TokenType) -> ParseResult<Token> {
@@ -528,20 +1291,99 @@ impl Parser {
             .ok_or(ParseError::ExpectedDifferentToken(self.err_ctx(), tt))
     }
 
+    /// Parses a comma separated list of expressions (allowing a trailing comma), stopping
+    /// before the closing token. A malformed element is reported and skipped over, so
+    /// the rest of the list is still checked for errors.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{parser::parse, scanner::scan};
+    ///
+    /// // A trailing comma is fine, but a comma on its own isn't.
+    /// let (tokens, _) = scan("f(1, 2,);\nprint([1, 2,]);");
+    /// assert!(!parse(tokens).1.had_error);
+    /// let (tokens, _) = scan("f(,);");
+    /// let (_, error_reporter) = parse(tokens);
+    /// assert_eq!(error_reporter.messages, vec!["[Line 1, Col 3] Error at ',': Expected Expression"]);
+    /// ```
+    fn expression_list(&mut self, closing: TokenType) -> ParseResult<Vec<Expr>> {
+        let mut elements = vec![];
+        if self.current_token_is_a(closing.clone()) {
+            return Ok(elements);
+        }
+
+        loop {
+            match self.expression() {
+                Ok(expr) => elements.push(expr),
+                Err(e) => {
+                    // If the list never finds its end, recover at the statement level instead.
+                    if !self.skip_list_element(&closing) {
+                        return Err(e);
+                    }
+                    self.error_reporter.error(e);
+                }
+            }
+
+            if !self.advance_on(TokenType::Comma) || self.current_token_is_a(closing.clone()) {
+                return Ok(elements);
+            }
+        }
+    }
+
+    /// Skips the rest of a malformed list element, up to the comma or closing token
+    /// that ends it. Returns false if the statement ends first.
+    fn skip_list_element(&mut self, closing: &TokenType) -> bool {
+        let mut nesting = 0usize;
+        while !self.is_at_end() {
+            match self.current_token().token_type {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => {
+                    nesting += 1
+                }
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+                    if nesting > 0 =>
+                {
+                    nesting -= 1
+                }
+                tt if tt == *closing || tt == TokenType::Comma => return true,
+                TokenType::SemiColon
+                | TokenType::RightParen
+                | TokenType::RightBracket
+                | TokenType::RightBrace => return false,
+                _ => {}
+            }
+            self.advance();
+        }
+        false
+    }
+
     /// Tries to bring the parser to a statement boundary when an error is encountered.
     fn synchronize(&mut self) {
+        // Leave the closing brace of a block for the block to consume, otherwise
+        // the block would swallow everything after it looking for its end.
+        if self.depth > 0 && self.current_token_is_a(TokenType::RightBrace) {
+            return;
+        }
         self.advance();
 
-        // Advance until the previoous token is a Semi-colon or
-        // the current colon is a keywordused to start a statement.
+        // Advance until the previous token is a semicolon, the current one is a
+        // keyword used to start a statement, or we hit the end of the enclosing block.
         while !self.is_at_end() {
             if self.previous_token().token_type == TokenType::SemiColon
-                || vec![
+                || (self.depth > 0 && self.current_token_is_a(TokenType::RightBrace))
+                || [
+                    TokenType::Break,
                     TokenType::Class,
+                    TokenType::Const,
+                    TokenType::Continue,
+                    TokenType::Do,
                     TokenType::For,
+                    TokenType::Foreach,
                     TokenType::Fun,
                     TokenType::If,
+                    TokenType::Import,
+                    TokenType::Repeat,
                     TokenType::Return,
+                    TokenType::Throw,
+                    TokenType::Try,
                     TokenType::Var,
                     TokenType::While,
                 ]
@@ -563,7 +1405,7 @@ impl Parser {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     /// Will advance the current token if it has the given token type.
@@ -572,6 +1414,13 @@ impl Parser {
         self.advance_on_any_of(vec![token_type])
     }
 
+    /// Checks whether the token after the current one is a particular token type
+    fn next_token_is_a(&self, tt: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.token_type == tt)
+    }
+
     /// Checks whether the current token is a particular token type
     fn current_token_is_a(&mut self, tt: TokenType) -> bool {
         if self.is_at_end() {
@@ -617,3 +1466,31 @@ impl Parser {
             .clone()
     }
 }
+
+/// The keyword of a statement that always jumps somewhere else (`return`, `break`,
+/// `continue` or `throw`), or `None` for any other statement.
+fn jump_keyword(stmt: &Stmt) -> Option<&Token> {
+    match stmt {
+        Stmt::Return(ReturnStmt { return_keyword, .. }) => Some(return_keyword),
+        Stmt::Break(BreakStmt { keyword, .. })
+        | Stmt::Continue(ContinueStmt { keyword, .. })
+        | Stmt::Throw(ThrowStmt { keyword, .. }) => Some(keyword),
+        _ => None,
+    }
+}
+
+/// Turns the operator of a compound assignment (like `+=`) into the binary operator
+/// it applies (like `+`), keeping its position for error reporting.
+fn binary_operator_of(compound_operator: Token) -> Token {
+    let token_type = match compound_operator.token_type {
+        TokenType::PlusEqual => TokenType::Plus,
+        TokenType::MinusEqual => TokenType::Minus,
+        TokenType::StarEqual => TokenType::Star,
+        _ => TokenType::Slash,
+    };
+    Token {
+        lexeme: token_type.to_string(),
+        token_type,
+        ..compound_operator
+    }
+}
@@ -1,3 +1,434 @@
 //! At this point, I'm really feeling the pain of not sticking to the Visitor Pattern the book uses.
-//! But I get the gist. We want to know for each local variable how many scopes deep it was 
+//! But I get the gist. We want to know for each local variable how many scopes deep it was
 //! declared, and if we dont find it there, we use the global variable.
+use std::collections::HashMap;
+
+use crate::{
+    error::{error_reporter::ErrorReporter, resolve_error::ResolveError, warning::Warning},
+    grammar::{
+        ArrayExpr, AssignmentExpr, BinaryExpr, BlockStmt, CallExpr, ClassDeclarationStmt,
+        DoWhileStmt, Expr, ExpressionStmt, ForEachStmt, FunctionDeclarationStmt, GetExpr,
+        GroupingExpr, IfStmt, IncDecExpr, IndexExpr, IndexSetExpr, InterpolationExpr, RepeatStmt,
+        ReturnStmt, SetExpr, SliceExpr, Stmt, TernaryExpr, ThisExpr, ThrowStmt, TryStmt, UnaryExpr,
+        VariableDeclarationStmt, VariableExpr, WhileStmt,
+    },
+    token::Token,
+};
+
+/// The resolver walks the syntax tree once before it's interpreted, and works out which
+/// scope each variable refers to. Without this, variables are looked up by name at runtime,
+/// so a closure can end up seeing a variable declared *after* it in an enclosing block.
+pub struct Resolver {
+    /// The statements being resolved. The depths get written directly into them.
+    stmts: Vec<Stmt>,
+
+    /// A stack of the local scopes currently being resolved, innermost last. The global
+    /// scope isn't tracked, anything we can't find is assumed to be global.
+    scopes: Vec<HashMap<String, Local>>,
+
+    /// Enrichable object for tracking static errors
+    error_reporter: ErrorReporter,
+
+    /// Whether to warn about local variables which are never read. Turned off for
+    /// the REPL, where a half finished block is nothing to worry about.
+    pub warn_unused_locals: bool,
+}
+
+/// What the resolver knows about a local variable.
+#[derive(Default)]
+struct Local {
+    /// Whether it has finished being initialized.
+    is_defined: bool,
+
+    /// Whether it's been read anywhere.
+    is_read: bool,
+
+    /// Where it was declared, for variables declared with `var` (or `const`). Those
+    /// are the only ones it's a mistake to never read, since parameters and the like
+    /// are often left unused on purpose.
+    declaration: Option<Token>,
+}
+
+impl Resolver {
+    /// Constructs a new resolver for the statements produced by the parser.
+    pub fn new(stmts: Vec<Stmt>, error_reporter: ErrorReporter) -> Self {
+        Self {
+            stmts,
+            scopes: vec![],
+            error_reporter,
+            warn_unused_locals: true,
+        }
+    }
+
+    /// Resolves every variable in the program, returning the statements with
+    /// their scope depths filled in. Mistakes that can be caught without running
+    /// the program are reported along the way.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{
+    ///     error::error_reporter::ErrorReporter, lox::run_source, parser::parse, resolver::Resolver,
+    ///     scanner::scan,
+    /// };
+    ///
+    /// // A local variable can't be read in its own initializer, even if there's
+    /// // an outer variable with the same name.
+    /// let errors = run_source("{ var a = a; }").unwrap_err();
+    /// assert_eq!(errors, vec!["[Line 1, Col 11] Error at 'a': Can't read a local variable in its own initializer"]);
+    /// let errors = run_source("var a = 1;\n{ var a = a + 1; }").unwrap_err();
+    /// assert_eq!(errors, vec!["[Line 2, Col 11] Error at 'a': Can't read a local variable in its own initializer"]);
+    ///
+    /// // Declaring the same local variable twice is a mistake, but globals can be redeclared.
+    /// let errors = run_source("{ var x = 1; var x = 2; }").unwrap_err();
+    /// assert_eq!(errors, vec!["[Line 1, Col 18] Error at 'x': Already a variable with this name in this scope"]);
+    /// assert!(run_source("var x = 1; var x = 2;").is_ok());
+    ///
+    /// // A local variable that's never read gets a warning.
+    /// let (tokens, _) = scan("{ var unused = 1; var used = 2; print(used); }");
+    /// let (stmts, _) = parse(tokens);
+    /// let (_, error_reporter) = Resolver::new(stmts, ErrorReporter::new()).resolve();
+    /// assert_eq!(error_reporter.warnings, vec!["[Line 1, Col 7] Warning at 'unused': Local variable 'unused' is never read"]);
+    ///
+    /// // Unless `locals()` is called in its scope after it's declared.
+    /// let (tokens, _) = scan("{ var listed = 1; print(locals()); var unlisted = 2; }");
+    /// let (stmts, _) = parse(tokens);
+    /// let (_, error_reporter) = Resolver::new(stmts, ErrorReporter::new()).resolve();
+    /// assert_eq!(error_reporter.warnings, vec!["[Line 1, Col 40] Warning at 'unlisted': Local variable 'unlisted' is never read"]);
+    /// ```
+    pub fn resolve(mut self) -> (Vec<Stmt>, ErrorReporter) {
+        let mut stmts = std::mem::take(&mut self.stmts);
+        self.resolve_stmts(&mut stmts);
+        (stmts, self.error_reporter)
+    }
+
+    fn resolve_stmts(&mut self, stmts: &mut [Stmt]) {
+        // Functions declared side by side in a local scope can call each other, so their
+        // names need to be known before any of their bodies are resolved. Globals don't
+        // need this since anything unresolved is looked up globally anyway.
+        for stmt in stmts.iter() {
+            if let Stmt::FunctionDeclaration(function) = stmt {
+                self.declare(&function.name);
+                self.define(&function.name);
+            }
+        }
+
+        for stmt in stmts.iter_mut() {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Expression(ExpressionStmt { expr }) => self.resolve_expr(expr),
+            Stmt::VariableDeclaration(VariableDeclarationStmt {
+                name, initializer, ..
+            }) => {
+                // Declare first so the name shadows any outer variable while the
+                // initializer is resolved, then mark it as ready to use.
+                self.declare(name);
+                if let Some(local) = self
+                    .scopes
+                    .last_mut()
+                    .and_then(|scope| scope.get_mut(&name.lexeme))
+                {
+                    local.declaration = Some(name.clone());
+                }
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While(WhileStmt {
+                condition,
+                body,
+                increment,
+                ..
+            }) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            Stmt::DoWhile(DoWhileStmt {
+                body, condition, ..
+            }) => {
+                self.resolve_stmt(body);
+                self.resolve_expr(condition);
+            }
+            Stmt::Repeat(RepeatStmt { count, body, .. }) => {
+                self.resolve_expr(count);
+                self.resolve_stmt(body);
+            }
+            Stmt::ForEach(ForEachStmt {
+                variable,
+                iterable,
+                body,
+                ..
+            }) => {
+                self.resolve_expr(iterable);
+
+                // Each iteration gets its own scope holding the loop variable.
+                self.in_new_scope(|resolver| {
+                    resolver.declare(variable);
+                    resolver.define(variable);
+                    resolver.resolve_stmt(body);
+                });
+            }
+            Stmt::FunctionDeclaration(function) => {
+                // The name was already declared by `resolve_stmts`, so the body can call itself.
+                self.resolve_function(function);
+            }
+            Stmt::ClassDeclaration(ClassDeclarationStmt {
+                name,
+                methods,
+                getters,
+                class_methods,
+            }) => {
+                self.declare(name);
+                self.define(name);
+
+                // Methods get `this` bound in a scope wrapped around their closure. In a
+                // class method, `this` is the class.
+                self.in_new_scope(|resolver| {
+                    resolver
+                        .scopes
+                        .last_mut()
+                        .expect("Just pushed a scope")
+                        .insert(
+                            "this".to_owned(),
+                            Local {
+                                is_defined: true,
+                                ..Local::default()
+                            },
+                        );
+                    for method in methods
+                        .iter_mut()
+                        .chain(getters.iter_mut())
+                        .chain(class_methods.iter_mut())
+                    {
+                        resolver.resolve_function(method);
+                    }
+                });
+            }
+            Stmt::Block(BlockStmt { body }) => {
+                self.in_new_scope(|resolver| resolver.resolve_stmts(body));
+            }
+            Stmt::If(IfStmt {
+                condition,
+                then_branch,
+                else_branch,
+            }) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::Return(ReturnStmt { value, .. }) => {
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+            Stmt::Throw(ThrowStmt { value, .. }) => self.resolve_expr(value),
+
+            // The imported file is resolved on its own when it's run, and only
+            // touches globals here.
+            Stmt::Import(_) => {}
+            Stmt::Try(TryStmt {
+                body,
+                catch_variable,
+                catch_body,
+            }) => {
+                self.in_new_scope(|resolver| resolver.resolve_stmts(&mut body.body));
+
+                // Like a foreach, the caught error is bound in its own scope around the block.
+                self.in_new_scope(|resolver| {
+                    resolver.declare(catch_variable);
+                    resolver.define(catch_variable);
+                    resolver.in_new_scope(|resolver| resolver.resolve_stmts(&mut catch_body.body));
+                });
+            }
+        }
+    }
+
+    /// Resolves a function body. The parameters and the body share a single scope,
+    /// matching the one scope the interpreter adds when the function is called.
+    fn resolve_function(&mut self, function: &mut FunctionDeclarationStmt) {
+        self.in_new_scope(|resolver| {
+            for param in function.params.iter_mut() {
+                // A default can refer to the parameters before it, but not itself.
+                if let Some(default) = &mut param.default {
+                    resolver.resolve_expr(default);
+                }
+                resolver.declare(&param.name);
+                resolver.define(&param.name);
+            }
+            resolver.resolve_stmts(&mut function.body);
+        });
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Variable(VariableExpr { name, depth }) => {
+                // A variable that's declared but not yet defined in the innermost scope
+                // is being read by its own initializer, like `var a = a;`.
+                if self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.lexeme))
+                    .is_some_and(|local| !local.is_defined)
+                {
+                    self.error_reporter
+                        .error(ResolveError::ReadInOwnInitializer(name.clone().into()));
+                }
+                *depth = self.resolve_read(name);
+
+                // `locals()` reads every variable declared so far in the scope it's called in.
+                if depth.is_none() && name.lexeme == "locals" {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.values_mut().for_each(|local| local.is_read = true);
+                    }
+                }
+            }
+            Expr::Assignment(AssignmentExpr {
+                variable,
+                expr,
+                depth,
+            }) => {
+                self.resolve_expr(expr);
+                *depth = self.resolve_local(variable);
+            }
+            Expr::This(ThisExpr { keyword, depth }) => *depth = self.resolve_local(keyword),
+            Expr::IncDec(IncDecExpr {
+                variable, depth, ..
+            }) => *depth = self.resolve_read(variable),
+            Expr::Binary(BinaryExpr { lhs, rhs, .. })
+            | Expr::Logical(BinaryExpr { lhs, rhs, .. }) => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            Expr::Ternary(TernaryExpr {
+                condition,
+                then_expr,
+                else_expr,
+            }) => {
+                self.resolve_expr(condition);
+                self.resolve_expr(then_expr);
+                self.resolve_expr(else_expr);
+            }
+            Expr::Grouping(GroupingExpr { expr }) => self.resolve_expr(expr),
+            Expr::Literal(_) => {}
+            Expr::Unary(UnaryExpr { rhs, .. }) => self.resolve_expr(rhs),
+            Expr::Call(CallExpr { callee, args, .. }) => {
+                self.resolve_expr(callee);
+                for arg in args.iter_mut() {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::Get(GetExpr { object, .. }) => self.resolve_expr(object),
+            Expr::Set(SetExpr { object, value, .. }) => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::Interpolation(InterpolationExpr { parts }) => {
+                for part in parts.iter_mut() {
+                    self.resolve_expr(part);
+                }
+            }
+            Expr::Array(ArrayExpr { elements }) => {
+                for element in elements.iter_mut() {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Index(IndexExpr { object, index, .. }) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::IndexSet(IndexSetExpr {
+                object,
+                index,
+                value,
+                ..
+            }) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Slice(SliceExpr {
+                object, start, end, ..
+            }) => {
+                self.resolve_expr(object);
+                for bound in [start, end].into_iter().flatten() {
+                    self.resolve_expr(bound);
+                }
+            }
+        }
+    }
+
+    /// Perform some operation inside an extra scope. Mirrors `Environment::in_new_local_scope`.
+    /// Once the scope is finished, any variables in it which were never read get a warning.
+    fn in_new_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let res = f(self);
+        let scope = self.scopes.pop().expect("Just pushed a scope");
+
+        if self.warn_unused_locals {
+            let mut unused: Vec<Token> = scope
+                .into_values()
+                .filter(|local| !local.is_read)
+                .filter_map(|local| local.declaration)
+                .filter(|name| !name.lexeme.starts_with('_'))
+                .collect();
+            unused.sort_by_key(|name| (name.line, name.column));
+            for name in unused {
+                self.error_reporter
+                    .warning(Warning::UnusedVariable(name.clone().into(), name.lexeme));
+            }
+        }
+        res
+    }
+
+    /// Adds a variable to the innermost scope, marked as not yet initialized.
+    /// Globals aren't tracked, so this does nothing at the top level (which is also
+    /// what lets globals be redeclared, handy in the REPL).
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if scope
+                .insert(name.lexeme.clone(), Local::default())
+                .is_some()
+            {
+                self.error_reporter
+                    .error(ResolveError::AlreadyDeclared(name.clone().into()));
+            }
+        }
+    }
+
+    /// Marks a variable in the innermost scope as initialized and ready to use.
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.entry(name.lexeme.clone()).or_default().is_defined = true;
+        }
+    }
+
+    /// Resolves a variable which is being read, marking it as used.
+    fn resolve_read(&mut self, name: &Token) -> Option<usize> {
+        let depth = self.resolve_local(name);
+        if let Some(depth) = depth {
+            let i = self.scopes.len() - 1 - depth;
+            if let Some(local) = self.scopes[i].get_mut(&name.lexeme) {
+                local.is_read = true;
+            }
+        }
+        depth
+    }
+
+    /// Finds how many scopes out from the innermost one a variable was declared in.
+    /// Returns `None` if it isn't in any local scope, meaning it must be a global.
+    fn resolve_local(&self, name: &Token) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme))
+    }
+}
@@ -4,14 +4,45 @@ use crate::{
         scan_error::{ScanError, ScanErrorCtx},
     },
     token::{Token, TokenType},
-    util::{is_alpha, is_alpha_numeric, is_digit, keywords, strip_quotes},
+    util::{is_alpha, is_alpha_numeric, is_digit, underscores_between_digits},
 };
 
+/// Scans some source code into tokens, with a fresh error reporter holding any
+/// errors found along the way.
+/// # Example
+/// ```
+/// use rust_lox_impl::{scanner::scan, token::TokenType};
+///
+/// let (tokens, error_reporter) = scan("1 + 2");
+/// let token_types: Vec<TokenType> = tokens.into_iter().map(|token| token.token_type).collect();
+/// assert_eq!(
+///     token_types,
+///     vec![TokenType::Number(1.0), TokenType::Plus, TokenType::Number(2.0), TokenType::Eof]
+/// );
+/// assert!(!error_reporter.had_error);
+///
+/// // A comment can run right up to the end of the source, without a newline.
+/// let (tokens, error_reporter) = scan("1; // end");
+/// let token_types: Vec<TokenType> = tokens.into_iter().map(|token| token.token_type).collect();
+/// assert_eq!(token_types, vec![TokenType::Number(1.0), TokenType::SemiColon, TokenType::Eof]);
+/// assert!(!error_reporter.had_error);
+///
+/// // Columns count from the start of the token's own line.
+/// let (tokens, _) = scan("var a = 1;\nprint(a);");
+/// let second_on_line_two = &tokens[6];
+/// assert_eq!(second_on_line_two.lexeme, "(");
+/// assert_eq!((second_on_line_two.line, second_on_line_two.column), (2, 6));
+/// ```
+pub fn scan(src: &str) -> (Vec<Token>, ErrorReporter) {
+    Scanner::new(src.to_owned(), ErrorReporter::new()).scan_tokens()
+}
+
 /// The scanner class is used to take raw source code as a string and produce a Vector of tokens, as well
 /// as to report any errors encountered in the process.
 pub struct Scanner {
-    /// The original source code as a String
-    source: String,
+    /// The original source code, split into characters up front so that `start` and
+    /// `current` can index straight into it.
+    source: Vec<char>,
 
     /// Used to collect the tokens as the source code is lexed
     tokens: Vec<Token>,
@@ -26,37 +57,113 @@ pub struct Scanner {
     /// is found in source.
     line: usize,
 
+    /// How many characters into the current line we are. Reset every time a \n
+    /// is found in source.
+    column: usize,
+
+    /// The column the token currently being scanned started on.
+    start_column: usize,
+
+    /// One entry for each `${` we're currently inside of in an interpolated string,
+    /// counting the braces opened within the embedded expression. When a `}` turns
+    /// up with no braces left open, the expression is over and the string carries on.
+    interpolations: Vec<usize>,
+
     /// Enrichable object for tracking static errors through scanning and parsing
     error_reporter: ErrorReporter,
 }
 
 impl Scanner {
     /// Generates a new scanner from the source code and a reference to the Lox class (for reporting errors that outlive the Scanner)
+    ///
+    /// The source is split into characters once here, so scanning takes time in proportion
+    /// to the length of the source.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{error::error_reporter::ErrorReporter, scanner::Scanner, token::{Token, TokenType}};
+    ///
+    /// let (tokens, _) = Scanner::new("é = \"ü\";".to_owned(), ErrorReporter::new()).scan_tokens();
+    /// assert_eq!(
+    ///     tokens,
+    ///     vec![
+    ///         Token::new(TokenType::Identifier, "é".to_owned(), 1, 1),
+    ///         Token::new(TokenType::Equal, "=".to_owned(), 1, 3),
+    ///         Token::new(TokenType::String("ü".to_owned()), "\"ü\"".to_owned(), 1, 5),
+    ///         Token::new(TokenType::SemiColon, ";".to_owned(), 1, 8),
+    ///         Token::new(TokenType::Eof, "".to_owned(), 1, 9),
+    ///     ]
+    /// );
+    ///
+    /// // A big source scans quickly (each character used to be found by counting from
+    /// // the start of the source, which took minutes for one this size).
+    /// let line = "var total = total + 1; // keep counting\n";
+    /// let src = line.repeat(20_000);
+    /// let start = std::time::Instant::now();
+    /// let (tokens, error_reporter) = Scanner::new(src, ErrorReporter::new()).scan_tokens();
+    /// assert!(start.elapsed().as_secs() < 10);
+    /// assert!(!error_reporter.had_error);
+    /// assert_eq!(tokens.len(), 20_000 * 7 + 1);
+    /// assert_eq!(tokens[tokens.len() - 2].line, 20_000);
+    /// ```
     pub fn new(source: String, error_reporter: ErrorReporter) -> Self {
         Self {
-            source,
+            source: source.chars().collect(),
             tokens: vec![],
             start: 0,
             current: 0,
             line: 1,
+            column: 0,
+            start_column: 1,
+            interpolations: vec![],
             error_reporter,
         }
     }
 
     fn err_ctx(&self) -> ScanErrorCtx {
-        self.line.into()
+        ScanErrorCtx {
+            line: self.line,
+            column: self.column,
+        }
     }
 
     /// Scans the source code and produces a Vector of Tokens.
+    /// Errors are reported as they're found and scanning carries on, so every
+    /// error in the source gets reported.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::{error::error_reporter::ErrorReporter, scanner::Scanner, token::TokenType};
+    ///
+    /// let src = "var a = 1e999;\nvar b = 0xZZ;\nvar c = 2;";
+    /// let (tokens, error_reporter) = Scanner::new(src.to_owned(), ErrorReporter::new()).scan_tokens();
+    /// assert_eq!(
+    ///     error_reporter.messages,
+    ///     vec![
+    ///         "[Line 1, Col 9]: Invalid Number '1e999'",
+    ///         "[Line 2, Col 9]: Invalid Number '0xZZ'",
+    ///     ]
+    /// );
+    /// assert_eq!(tokens[tokens.len() - 3].token_type, TokenType::Number(2.0));
+    /// ```
     pub fn scan_tokens(mut self) -> (Vec<Token>, ErrorReporter) {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_column = self.column + 1;
             self.scan_token();
         }
 
+        // A string with an embedded expression that never finished.
+        if !self.interpolations.is_empty() {
+            self.error_reporter
+                .error(ScanError::UnterminatedString(self.err_ctx()));
+        }
+
         // Add an automatic EOF token when the end of the source code is reached.
-        self.tokens
-            .push(Token::new(TokenType::Eof, "".to_owned(), self.line));
+        self.tokens.push(Token::new(
+            TokenType::Eof,
+            "".to_owned(),
+            self.line,
+            self.column + 1,
+        ));
 
         (self.tokens, self.error_reporter)
     }
@@ -68,14 +175,76 @@ impl Scanner {
         match next_char {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(open_braces) = self.interpolations.last_mut() {
+                    *open_braces += 1;
+                }
+                self.add_token(TokenType::LeftBrace);
+            }
+            '}' => match self.interpolations.last_mut() {
+                // The end of an expression embedded in a string, so carry on with the string.
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string();
+                }
+                Some(open_braces) => {
+                    *open_braces -= 1;
+                    self.add_token(TokenType::RightBrace);
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            '.' => {
+                if self.current_char() == '.' && self.next_char() == '.' {
+                    self.advance();
+                    self.advance();
+                    self.add_token(TokenType::DotDotDot);
+                } else {
+                    self.add_token(TokenType::Dot);
+                }
+            }
+            '-' => {
+                if self.advance_on('=') {
+                    self.add_token(TokenType::MinusEqual);
+                } else if self.advance_on('-') {
+                    self.add_token(TokenType::MinusMinus);
+                } else {
+                    self.add_token(TokenType::Minus);
+                }
+            }
+            '+' => {
+                if self.advance_on('=') {
+                    self.add_token(TokenType::PlusEqual);
+                } else if self.advance_on('+') {
+                    self.add_token(TokenType::PlusPlus);
+                } else {
+                    self.add_token(TokenType::Plus);
+                }
+            }
             ';' => self.add_token(TokenType::SemiColon),
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                if self.advance_on('=') {
+                    self.add_token(TokenType::StarEqual);
+                } else if self.advance_on('*') {
+                    self.add_token(TokenType::StarStar);
+                } else {
+                    self.add_token(TokenType::Star);
+                }
+            }
+            '%' => self.add_token(TokenType::Percent),
+            '?' => {
+                if self.advance_on('?') {
+                    self.add_token(TokenType::QuestionQuestion);
+                } else {
+                    self.add_token(TokenType::Question);
+                }
+            }
+            ':' => self.add_token(TokenType::Colon),
+            '&' => self.add_token(TokenType::Ampersand),
+            '|' => self.add_token(TokenType::Pipe),
+            '^' => self.add_token(TokenType::Caret),
             '!' => {
                 if self.advance_on('=') {
                     self.add_token(TokenType::BangEqual);
@@ -93,6 +262,8 @@ impl Scanner {
             '<' => {
                 if self.advance_on('=') {
                     self.add_token(TokenType::LessEqual);
+                } else if self.advance_on('<') {
+                    self.add_token(TokenType::LessLess);
                 } else {
                     self.add_token(TokenType::Less);
                 }
@@ -100,25 +271,29 @@ impl Scanner {
             '>' => {
                 if self.advance_on('=') {
                     self.add_token(TokenType::GreaterEqual);
+                } else if self.advance_on('>') {
+                    self.add_token(TokenType::GreaterGreater);
                 } else {
                     self.add_token(TokenType::Greater);
                 }
             }
             '/' => {
                 if self.advance_on('/') {
-                    // Read to the end of a comment line
-                    while self.current_char() != '\n' && !self.is_at_end() {
+                    // Read to the end of a comment line, or the end of the source. Checking
+                    // for the end first means we never look past the last character.
+                    while !self.is_at_end() && self.current_char() != '\n' {
                         self.advance();
                     }
+                } else if self.advance_on('*') {
+                    self.block_comment();
+                } else if self.advance_on('=') {
+                    self.add_token(TokenType::SlashEqual);
                 } else {
                     self.add_token(TokenType::Slash);
                 }
             }
-            ' ' | '\r' | '\t' => {
-                // Ignore whitespace
-            }
-            '\n' => {
-                self.line += 1;
+            ' ' | '\r' | '\t' | '\n' => {
+                // Ignore whitespace. Newlines are counted by `advance`.
             }
             '"' => {
                 self.string();
@@ -136,49 +311,158 @@ impl Scanner {
         }
     }
 
-    /// Handles scanning in string values
+    /// Skips over a block comment. Block comments can be nested, i.e.
+    /// `/* a /* b */ c */` is a single comment.
+    fn block_comment(&mut self) {
+        // We've already consumed the opening `/*`
+        let mut depth = 1;
+
+        while depth > 0 && !self.is_at_end() {
+            if self.current_char() == '/' && self.next_char() == '*' {
+                self.advance();
+                depth += 1;
+            } else if self.current_char() == '*' && self.next_char() == '/' {
+                self.advance();
+                depth -= 1;
+            }
+
+            self.advance();
+        }
+
+        if depth > 0 {
+            self.error_reporter
+                .error(ScanError::UnterminatedBlockComment(self.err_ctx()));
+        }
+    }
+
+    /// Handles scanning in string values. Escape sequences are decoded as we go, so
+    /// the String token holds the actual characters rather than the raw source text.
+    /// A `${` in a string embeds an expression. The string up to that point becomes an
+    /// `Interpolation` token, the expression is scanned as normal tokens, and the rest
+    /// of the string is picked up again at the closing `}`.
     fn string(&mut self) {
+        let mut value = String::new();
+
         // Scan to the ending quotation mark
         while self.current_char() != '"' && !self.is_at_end() {
-            // Support multi line strings
-            if self.current_char() == '\n' {
-                self.line += 1;
+            match self.advance() {
+                '$' if self.current_char() == '{' => {
+                    self.advance();
+                    self.add_token(TokenType::Interpolation(value));
+                    self.interpolations.push(0);
+                    return;
+                }
+                '\\' if !self.is_at_end() => match self.escape_sequence() {
+                    Some(c) => value.push(c),
+                    None => self
+                        .error_reporter
+                        .error(ScanError::InvalidEscapeSequence(self.err_ctx())),
+                },
+                // Anything else, including newlines in a multi line string, is kept as is.
+                c => value.push(c),
             }
-
-            self.advance();
         }
 
         // If we reach the ending quotation before the end of the file, consume it then add the String token. Otherwise, report the error.
         if self.is_at_end() {
+            // Any strings this one was embedded in can't be finished either.
+            self.interpolations.clear();
             self.error_reporter
                 .error(ScanError::UnterminatedString(self.err_ctx()));
         } else {
             self.advance(); // Closing "
-            self.add_token(TokenType::String(strip_quotes(self.get_current_lexeme())));
+            self.add_token(TokenType::String(value));
+        }
+    }
+
+    /// Consumes the character after a `\` in a string literal and returns the character
+    /// it represents, or None if it isn't a valid escape sequence.
+    fn escape_sequence(&mut self) -> Option<char> {
+        match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '$' => Some('$'),
+            '0' => Some('\0'),
+            _ => None,
         }
     }
 
     /// Handles scanning number values
     fn number(&mut self) {
-        // Scan in all digits
-        while is_digit(self.current_char()) {
+        // Hex (0x1F) and binary (0b1010) integers. Everything alphanumeric after the
+        // prefix is taken as part of the number, so `0xZZ` is reported as one bad number.
+        let radix = match (self.get_current_lexeme().as_str(), self.current_char()) {
+            ("0", 'x' | 'X') => Some(16),
+            ("0", 'b' | 'B') => Some(2),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            self.advance();
+            while is_alpha_numeric(self.current_char()) {
+                self.advance();
+            }
+
+            let digits = self.get_current_lexeme()[2..].to_owned();
+            match u64::from_str_radix(&digits.replace('_', ""), radix) {
+                Ok(num) if underscores_between_digits(&digits, radix) => {
+                    self.add_token(TokenType::Number(num as f64))
+                }
+                _ => self.invalid_number(),
+            }
+            return;
+        }
+
+        // Scan in all digits. Underscores can be used to group them, like `1_000_000`,
+        // as long as each one is between two digits.
+        while is_digit(self.current_char()) || self.current_char() == '_' {
             self.advance();
         }
 
         // If there is a DOT character followed by more digits, scan in the DOT and the rest of the digits
         if self.current_char() == '.' && is_digit(self.next_char()) {
             self.advance();
-            while is_digit(self.current_char()) {
+            while is_digit(self.current_char()) || self.current_char() == '_' {
                 self.advance();
             }
         }
 
-        // Parse the number as an f64 and add the token for the number literal.
-        let num = self
-            .get_current_lexeme()
-            .parse::<f64>()
-            .expect("Could not parse f64 from number");
-        self.add_token(TokenType::Number(num));
+        // Scan in an exponent, like `2.5e-4`. The `e` has to be followed by digits.
+        if matches!(self.current_char(), 'e' | 'E') {
+            self.advance();
+            if matches!(self.current_char(), '+' | '-') {
+                self.advance();
+            }
+            if !is_digit(self.current_char()) {
+                self.invalid_number();
+                return;
+            }
+            while is_digit(self.current_char()) || self.current_char() == '_' {
+                self.advance();
+            }
+        }
+
+        // Parse the number as an f64 and add the token for the number literal. Anything
+        // too big to represent (like `1e999`) is an error rather than infinity.
+        let lexeme = self.get_current_lexeme();
+        match lexeme.replace('_', "").parse::<f64>() {
+            Ok(num) if num.is_finite() && underscores_between_digits(&lexeme, 10) => {
+                self.add_token(TokenType::Number(num))
+            }
+            _ => self.invalid_number(),
+        }
+    }
+
+    /// Reports the number being scanned as invalid, pointing at the start of it.
+    fn invalid_number(&mut self) {
+        let ctx = ScanErrorCtx {
+            line: self.line,
+            column: self.start_column,
+        };
+        self.error_reporter
+            .error(ScanError::InvalidNumber(ctx, self.get_current_lexeme()));
     }
 
     /// Handles scanning in Keywords and Identifiers.
@@ -190,14 +474,8 @@ impl Scanner {
 
         // Check to see if the current lexeme is one of Lox's keywords. If it is,
         // add the appropriate keyword token, otherwise add it as an identifier.
-        match keywords().get(&self.get_current_lexeme()) {
-            Some(keyword_token) => {
-                self.add_token(keyword_token.clone());
-            }
-            None => {
-                self.add_token(TokenType::Identifier);
-            }
-        }
+        let token_type = TokenType::keyword_or_identifier(&self.get_current_lexeme());
+        self.add_token(token_type);
     }
 
     /// Lets us know if we've made it to the end of the source code.
@@ -206,22 +484,29 @@ impl Scanner {
     }
 
     /// Advance current to encompass another character and return the previous character for evaluation.
+    /// Keeps the line and column up to date, so nothing else has to watch for newlines.
     fn advance(&mut self) -> char {
         self.current += 1;
-        self.source.chars().nth(self.current - 1).expect(&format!(
-            "Could not find {}th char in source",
-            self.current - 1
-        ))
+        let c = self.source[self.current - 1];
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+
+        c
     }
 
     /// Only advances current if the next char is the one we're looking for. Returns
     /// boolean indicating whether the character was found.
     fn advance_on(&mut self, expected: char) -> bool {
         if self.is_at_end() || self.current_char() != expected {
-            return false;
+            false
         } else {
             self.advance();
-            return true;
+            true
         }
     }
 
@@ -237,28 +522,21 @@ impl Scanner {
 
     /// Method for peeking at what the next characters are.
     fn peek_n_characters(&self, n: usize) -> char {
-        if self.current + n >= self.source.len() {
-            '\0'
-        } else {
-            self.source
-                .chars()
-                .nth(self.current + n)
-                .expect(&format!("No char {} characters ahead", n))
-        }
+        self.source.get(self.current + n).copied().unwrap_or('\0')
     }
 
     /// Returns the current subslice of the source code in view by the Scanner
     fn get_current_lexeme(&self) -> String {
-        self.source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect()
+        self.source[self.start..self.current].iter().collect()
     }
 
     /// Adds any token to the tokens list
     fn add_token(&mut self, token_type: TokenType) {
-        self.tokens
-            .push(Token::new(token_type, self.get_current_lexeme(), self.line));
+        self.tokens.push(Token::new(
+            token_type,
+            self.get_current_lexeme(),
+            self.line,
+            self.start_column,
+        ));
     }
 }
@@ -1,11 +1,14 @@
 /// Represents every valid Lox token.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenType {
     // Single character tokens
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -13,6 +16,12 @@ pub enum TokenType {
     SemiColon,
     Slash,
     Star,
+    Percent,
+    Question,
+    Colon,
+    Ampersand,
+    Pipe,
+    Caret,
 
     // One or two character tokens
     Bang,
@@ -23,31 +32,121 @@ pub enum TokenType {
     Greater,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    StarStar,
+    QuestionQuestion,
+    SlashEqual,
+    PlusPlus,
+    MinusMinus,
+
+    // Three character tokens
+    DotDotDot,
 
     // Literals
     Identifier,
     String(String),
     Number(f64),
 
+    /// The part of a string before an embedded `${expression}`.
+    Interpolation(String),
+
     // Keywords
     And,
+    Break,
+    Catch,
     Class,
+    Const,
+    Continue,
+    Do,
     Else,
     False,
     Fun,
     For,
+    Foreach,
     If,
+    Import,
+    In,
+    Is,
     Nil,
     Or,
+    Repeat,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
     Eof,
 }
 
+impl TokenType {
+    /// Looks up the keyword a lexeme spells out, or `Identifier` if it isn't a keyword.
+    /// # Example
+    /// ```
+    /// use rust_lox_impl::token::TokenType::{self, *};
+    ///
+    /// // Every keyword reads back as itself from how it's written.
+    /// let keywords = [
+    ///     And, Break, Catch, Class, Const, Continue, Do, Else, False, For, Foreach, Fun, If,
+    ///     Import, In, Is, Nil, Or, Repeat, Return, Super, This, Throw, True, Try, Var, While,
+    /// ];
+    /// for keyword in keywords {
+    ///     assert_eq!(TokenType::keyword_or_identifier(&keyword.to_string()), keyword);
+    /// }
+    ///
+    /// assert_eq!(TokenType::keyword_or_identifier("whilst"), TokenType::Identifier);
+    /// assert_eq!(TokenType::keyword_or_identifier("While"), TokenType::Identifier);
+    /// ```
+    pub fn keyword_or_identifier(lexeme: &str) -> Self {
+        match lexeme {
+            "and" => TokenType::And,
+            "break" => TokenType::Break,
+            "catch" => TokenType::Catch,
+            "class" => TokenType::Class,
+            "const" => TokenType::Const,
+            "continue" => TokenType::Continue,
+            "do" => TokenType::Do,
+            "else" => TokenType::Else,
+            "false" => TokenType::False,
+            "for" => TokenType::For,
+            "foreach" => TokenType::Foreach,
+            "fun" => TokenType::Fun,
+            "if" => TokenType::If,
+            "import" => TokenType::Import,
+            "in" => TokenType::In,
+            "is" => TokenType::Is,
+            "nil" => TokenType::Nil,
+            "or" => TokenType::Or,
+            "repeat" => TokenType::Repeat,
+            "return" => TokenType::Return,
+            "super" => TokenType::Super,
+            "this" => TokenType::This,
+            "throw" => TokenType::Throw,
+            "true" => TokenType::True,
+            "try" => TokenType::Try,
+            "var" => TokenType::Var,
+            "while" => TokenType::While,
+            _ => TokenType::Identifier,
+        }
+    }
+}
+
+/// Renders a token type the way it's written in Lox source, since this text ends up
+/// in error messages.
+/// # Example
+/// ```
+/// use rust_lox_impl::token::TokenType;
+///
+/// assert_eq!(TokenType::Comma.to_string(), ",");
+/// assert_eq!(TokenType::And.to_string(), "and");
+/// assert_eq!(TokenType::Or.to_string(), "or");
+/// ```
 impl std::fmt::Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -55,13 +154,21 @@ impl std::fmt::Display for TokenType {
             TokenType::RightParen => ")".to_owned(),
             TokenType::LeftBrace => "{".to_owned(),
             TokenType::RightBrace => "}".to_owned(),
-            TokenType::Comma => ".to_owned(),".to_owned(),
+            TokenType::LeftBracket => "[".to_owned(),
+            TokenType::RightBracket => "]".to_owned(),
+            TokenType::Comma => ",".to_owned(),
             TokenType::Dot => ".".to_owned(),
             TokenType::Minus => "-".to_owned(),
             TokenType::Plus => "+".to_owned(),
             TokenType::SemiColon => ";".to_owned(),
             TokenType::Slash => "/".to_owned(),
             TokenType::Star => "*".to_owned(),
+            TokenType::Percent => "%".to_owned(),
+            TokenType::Question => "?".to_owned(),
+            TokenType::Colon => ":".to_owned(),
+            TokenType::Ampersand => "&".to_owned(),
+            TokenType::Pipe => "|".to_owned(),
+            TokenType::Caret => "^".to_owned(),
             TokenType::Bang => "!".to_owned(),
             TokenType::BangEqual => "!=".to_owned(),
             TokenType::Equal => "=".to_owned(),
@@ -70,24 +177,47 @@ impl std::fmt::Display for TokenType {
             TokenType::Greater => ">".to_owned(),
             TokenType::Less => "<".to_owned(),
             TokenType::LessEqual => "<=".to_owned(),
+            TokenType::LessLess => "<<".to_owned(),
+            TokenType::GreaterGreater => ">>".to_owned(),
+            TokenType::PlusEqual => "+=".to_owned(),
+            TokenType::MinusEqual => "-=".to_owned(),
+            TokenType::StarEqual => "*=".to_owned(),
+            TokenType::StarStar => "**".to_owned(),
+            TokenType::QuestionQuestion => "??".to_owned(),
+            TokenType::SlashEqual => "/=".to_owned(),
+            TokenType::PlusPlus => "++".to_owned(),
+            TokenType::MinusMinus => "--".to_owned(),
+            TokenType::DotDotDot => "...".to_owned(),
             TokenType::Identifier => "identifier".to_owned(),
-            TokenType::String(s) => s.clone(),
+            TokenType::String(s) | TokenType::Interpolation(s) => s.clone(),
             TokenType::Number(n) => {
                 format!("{}", n)
             }
             TokenType::And => "and".to_owned(),
+            TokenType::Break => "break".to_owned(),
+            TokenType::Catch => "catch".to_owned(),
             TokenType::Class => "class".to_owned(),
+            TokenType::Const => "const".to_owned(),
+            TokenType::Continue => "continue".to_owned(),
+            TokenType::Do => "do".to_owned(),
             TokenType::Else => "else".to_owned(),
             TokenType::False => "false".to_owned(),
             TokenType::Fun => "fun".to_owned(),
             TokenType::For => "for".to_owned(),
+            TokenType::Foreach => "foreach".to_owned(),
             TokenType::If => "if".to_owned(),
+            TokenType::Import => "import".to_owned(),
+            TokenType::In => "in".to_owned(),
+            TokenType::Is => "is".to_owned(),
             TokenType::Nil => "nil".to_owned(),
             TokenType::Or => "or".to_owned(),
+            TokenType::Repeat => "repeat".to_owned(),
             TokenType::Return => "return".to_owned(),
             TokenType::Super => "super".to_owned(),
             TokenType::This => "this".to_owned(),
             TokenType::True => "true".to_owned(),
+            TokenType::Throw => "throw".to_owned(),
+            TokenType::Try => "try".to_owned(),
             TokenType::Var => "var".to_owned(),
             TokenType::While => "while".to_owned(),
             TokenType::Eof => "eof".to_owned(),
@@ -99,6 +229,7 @@ impl std::fmt::Display for TokenType {
 
 /// Represents a valid Lox token.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     /// Represents the type of the token. Contains the associated literal for Strings and Numbers.
     pub token_type: TokenType,
@@ -108,15 +239,19 @@ pub struct Token {
 
     /// The line the particular token was found on.
     pub line: usize,
+
+    /// The column (in characters, starting from 1) the token starts on.
+    pub column: usize,
 }
 
 impl Token {
     /// Standard constructor
-    pub fn new(token_type: TokenType, lexeme: String, line: usize) -> Token {
+    pub fn new(token_type: TokenType, lexeme: String, line: usize, column: usize) -> Token {
         Self {
             token_type,
             lexeme,
             line,
+            column,
         }
     }
 }